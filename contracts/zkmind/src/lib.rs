@@ -1,7 +1,10 @@
 #![no_std]
 
+use soroban_sdk::token::TokenClient;
+use soroban_sdk::xdr::{ScErrorType, ToXdr};
 use soroban_sdk::{
-    contract, contractclient, contracterror, contractimpl, contracttype, Address, BytesN, Env, Vec,
+    contract, contractclient, contracterror, contractevent, contractimpl, contracttype, Address,
+    Bytes, BytesN, Env, Map, String, TryFromVal, Val, Vec,
 };
 
 // ============================================================================
@@ -23,6 +26,41 @@ pub trait GameHub {
     fn end_game(env: Env, session_id: u32, player1_won: bool);
 }
 
+// ============================================================================
+// Ratings Interface
+// ============================================================================
+
+/// Source of each player's current points, passed to the hub's `start_game`.
+#[contractclient(name = "RatingsClient")]
+pub trait Ratings {
+    fn get_player_points(env: Env, player: Address) -> i128;
+}
+
+// ============================================================================
+// Observer Interface
+// ============================================================================
+
+/// External listener (chat bots, stream overlays) told about every move.
+#[contractclient(name = "ObserverClient")]
+pub trait Observer {
+    fn on_move(env: Env, session_id: u32, phase: GamePhase, guess_count: u32);
+}
+
+// ============================================================================
+// Verifier Interface (UltraHonk)
+// ============================================================================
+
+#[contractclient(name = "VerifierClient")]
+pub trait Verifier {
+    /// `public_inputs` is the circuit's public inputs as 32-byte big-endian
+    /// fields: commitment, guess pegs, red pegs, white pegs.
+    fn verify_proof(env: Env, public_inputs: Bytes, proof_bytes: Bytes) -> bool;
+
+    /// Same check against a verification key supplied by the caller, so the
+    /// verifier skips loading its own.
+    fn verify_proof_with_vk(env: Env, vk: Bytes, public_inputs: Bytes, proof_bytes: Bytes) -> bool;
+}
+
 // ============================================================================
 // Errors
 // ============================================================================
@@ -39,6 +77,43 @@ pub enum Error {
     MaxGuessesReached = 6,
     InvalidFeedback = 7,
     GameAlreadyEnded = 8,
+    InvalidConfig = 9,
+    InvalidSessionId = 10,
+    NotPlayer = 11,
+    NoStake = 12,
+    AlreadySettled = 13,
+    NotWinner = 14,
+    NoWinner = 15,
+    TimeoutNotReached = 16,
+    InvalidReveal = 17,
+    CommitmentMismatch = 18,
+    AlreadyRevealed = 19,
+    AlreadyMigrated = 20,
+    MissingProof = 21,
+    NoFeedbackYet = 22,
+    HintsNotAllowed = 23,
+    HintLimitReached = 24,
+    InvalidProof = 25,
+    Sealed = 26,
+    BatchTooLarge = 27,
+    EscrowOutstanding = 28,
+    TooManyActiveGames = 29,
+    NotInitialized = 30,
+    DuplicateProof = 31,
+    Paused = 32,
+    BlobTooLarge = 33,
+    DisputeWindowOpen = 34,
+    AlreadyCommitted = 35,
+    ProofIndexMismatch = 36,
+    PracticeGame = 37,
+    HubNotSet = 38,
+    HubCallFailed = 39,
+    HubRejected = 40,
+    InvalidLabels = 41,
+    TokenNotAllowed = 42,
+    GameAlreadyExists = 43,
+    AlreadyReported = 44,
+    RevealPending = 45,
 }
 
 // ============================================================================
@@ -53,6 +128,47 @@ pub enum GamePhase {
     WaitingForGuess = 1,
     WaitingForFeedback = 2,
     Finished = 3,
+    WaitingForAccept = 4,
+}
+
+/// Hash the codemaker used to build their commitment, so `reveal_code` can
+/// recompute it on-chain.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum CommitScheme {
+    Sha256 = 0,
+    Keccak256 = 1,
+}
+
+/// How a finished game was decided. `Unfinished` until the game ends.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum FinishReason {
+    Unfinished = 0,
+    Solved = 1,
+    Exhausted = 2,
+    Resigned = 3,
+    Timeout = 4,
+    AdminSettled = 5,
+    Draw = 6,
+    Forfeit = 7,
+    /// A strict game's reveal showed a recorded feedback was false.
+    FalseFeedback = 8,
+}
+
+/// What a given player is expected to do next.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum ActionHint {
+    Accept = 0,
+    Commit = 1,
+    Guess = 2,
+    GiveFeedback = 3,
+    Wait = 4,
+    GameOver = 5,
 }
 
 #[contracttype]
@@ -63,6 +179,14 @@ pub struct Feedback {
     pub proof_hash: BytesN<32>,
 }
 
+/// A peg of the secret the codemaker disclosed as a hint.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Hint {
+    pub position: u32,
+    pub color: u32,
+}
+
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct GameState {
@@ -77,6 +201,291 @@ pub struct GameState {
     pub max_guesses: u32,
     pub winner: Option<Address>,
     pub current_guess: Vec<u32>,
+    pub code_length: u32,
+    pub num_colors: u32,
+    pub created_ledger: u32,
+    pub finished_ledger: Option<u32>,
+    pub randomness_anchor: BytesN<32>,
+    pub pipeline_depth: u32,
+    /// Guesses awaiting feedback, oldest first. `current_guess` mirrors the head.
+    pub pending_guesses: Vec<Vec<u32>>,
+    pub stake_token: Option<Address>,
+    pub codemaker_stake: i128,
+    pub codebreaker_stake: i128,
+    pub draw_on_exhaustion: bool,
+    /// Escrow has been paid out or refunded.
+    pub settled: bool,
+    pub phase_entered_ledger: u32,
+    pub revealed: bool,
+    pub commit_scheme: CommitScheme,
+    pub finish_reason: FinishReason,
+    pub trusted: bool,
+    pub hints_allowed: bool,
+    pub strict_proofs: bool,
+    pub color_labels: Vec<String>,
+    /// The outcome has been sent to the Game Hub.
+    pub reported: bool,
+    pub feedback_mode: u32,
+    /// Each hint costs the codebreaker one guess.
+    pub hints: Vec<Hint>,
+    /// May submit guesses on the codebreaker's behalf.
+    pub guess_agent: Option<Address>,
+    /// The opened secret; empty until `revealed`.
+    pub secret: Vec<u32>,
+    pub validity_proof: bool,
+    pub sealed_feedback_allowed: bool,
+    pub fair_play: FairPlay,
+}
+
+/// What the codemaker committed to besides the secret itself. Kept apart from
+/// `GameState`, which is at the field limit for contract types.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FairPlay {
+    /// Provably-fair games: sha256 of the codemaker's seed, and the ledger
+    /// entropy mixed in when it was committed.
+    pub seed_hash: Option<BytesN<32>>,
+    pub seed_entropy: Option<BytesN<32>>,
+    /// Hash of the proof that the committed secret is well-formed.
+    pub validity_proof_hash: Option<BytesN<32>>,
+    /// `sha256(correct_position || correct_color || nonce)` for the oldest
+    /// pending guess, if the codemaker sealed one.
+    pub sealed_feedback: Option<BytesN<32>>,
+    /// Secret encrypted by the codemaker at commit time, and the key they
+    /// publish once the game is over. Never decrypted on-chain; auditors
+    /// check offline that it opens to the committed secret.
+    pub encrypted_secret: Option<Bytes>,
+    pub decryption_key: Option<Bytes>,
+    /// Ledgers after creation by which the secret must be committed, or
+    /// `poke` cancels the game.
+    pub commit_deadline_ledgers: Option<u32>,
+}
+
+/// `GameState` as written by the original deployment, before rules, timings
+/// and stakes were tracked. Only read by `migrate`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LegacyGameState {
+    pub session_id: u32,
+    pub codemaker: Address,
+    pub codebreaker: Address,
+    pub phase: GamePhase,
+    pub commitment: BytesN<32>,
+    pub guesses: Vec<Vec<u32>>,
+    pub feedbacks: Vec<Feedback>,
+    pub guess_count: u32,
+    pub max_guesses: u32,
+    pub winner: Option<Address>,
+    pub current_guess: Vec<u32>,
+}
+
+/// Self-contained record of a game for sharing or replaying off-chain.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Transcript {
+    pub session_id: u32,
+    pub codemaker: Address,
+    pub codebreaker: Address,
+    pub code_length: u32,
+    pub num_colors: u32,
+    pub max_guesses: u32,
+    pub feedback_mode: u32,
+    pub commitment: BytesN<32>,
+    pub guesses: Vec<Vec<u32>>,
+    pub feedbacks: Vec<Feedback>,
+    /// Index of the feedback that declared the guess to be the secret.
+    pub solved_at: Option<u32>,
+    pub winner: Option<Address>,
+    pub created_ledger: u32,
+    pub finished_ledger: Option<u32>,
+    pub finish_reason: FinishReason,
+    pub hints: Vec<Hint>,
+}
+
+/// What an automated solver needs from a game, without addresses or
+/// settlement details.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SolverState {
+    pub code_length: u32,
+    pub num_colors: u32,
+    /// Always true: a secret may repeat colors.
+    pub allow_duplicates: bool,
+    pub feedback_mode: u32,
+    pub guesses: Vec<Vec<u32>>,
+    pub feedbacks: Vec<Feedback>,
+    pub remaining_guesses: u32,
+}
+
+/// Rules chosen when a game is created.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GameConfig {
+    pub code_length: u32,
+    pub num_colors: u32,
+    pub max_guesses: u32,
+    /// Guesses the codebreaker may queue ahead of feedback (1 = no pipelining).
+    pub pipeline_depth: u32,
+    /// Token both players escrow, or `None` for an unstaked game.
+    pub stake_token: Option<Address>,
+    pub codemaker_stake: i128,
+    pub codebreaker_stake: i128,
+    /// Running out of guesses is a draw rather than a codemaker win.
+    pub draw_on_exhaustion: bool,
+    pub commit_scheme: CommitScheme,
+    /// Feedback is taken on trust, so no proof hash is required.
+    pub trusted: bool,
+    /// The codemaker may disclose pegs via `give_hint`.
+    pub hints_allowed: bool,
+    /// A proof the verifier rejects forfeits the game to the codebreaker
+    /// instead of just being refused, and a proof hash may not be reused.
+    pub strict_proofs: bool,
+    /// Display name for each color index, or empty to leave naming to clients.
+    pub color_labels: Vec<String>,
+    /// `FEEDBACK_SPLIT` (red and white pegs) or `FEEDBACK_COMBINED` (one
+    /// score for all pegs of a right color, see `submit_score`).
+    pub feedback_mode: u32,
+    /// The codemaker must commit with `commit_code_with_validity_proof`, so
+    /// the secret is proven well-formed before the first guess.
+    pub validity_proof: bool,
+    /// The codemaker may seal each feedback in advance with `seal_feedback`,
+    /// so a feedback timeout reveals it instead of ending the game.
+    pub sealed_feedback_allowed: bool,
+    /// Ledgers after creation by which the codemaker must commit, after which
+    /// anyone may cancel the game with `poke`. `None` leaves only the usual
+    /// commit timeout.
+    pub commit_deadline_ledgers: Option<u32>,
+}
+
+/// Single-elimination bracket played out as ordinary games.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Tournament {
+    pub players: Vec<Address>,
+    pub round: u32,
+    /// Session ids of the current round's matches, in bracket order.
+    pub matches: Vec<u32>,
+    /// The current round's players, two per match in bracket order.
+    pub entrants: Vec<Address>,
+    /// Winners the admin awarded for matches that cannot be played out.
+    pub walkovers: Map<u32, Address>,
+    pub champion: Option<Address>,
+}
+
+/// One game to create with `new_games`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GameSpec {
+    pub codemaker: Address,
+    pub codebreaker: Address,
+    pub config: GameConfig,
+}
+
+/// Two games played side by side, each player making a code for the other.
+/// Whoever solves the opponent's code in fewer guesses wins.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DualGame {
+    pub player_a: Address,
+    pub player_b: Address,
+    /// Session in which `player_a` makes the code and `player_b` breaks it.
+    pub game_a: u32,
+    /// Session in which `player_b` makes the code and `player_a` breaks it.
+    pub game_b: u32,
+    pub decided: bool,
+    /// `None` once decided means a draw.
+    pub winner: Option<Address>,
+}
+
+/// Every contract the game is wired to.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Addresses {
+    pub admin: Address,
+    pub game_hub: Address,
+    pub verifier: Address,
+    pub treasury: Option<Address>,
+}
+
+/// Which game event an `EventRecord` stands for.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum EventKind {
+    Guess = 0,
+    Feedback = 1,
+    Hint = 2,
+    LastChance = 3,
+    Forfeited = 4,
+    TimedOut = 5,
+    Solved = 6,
+    Exhausted = 7,
+    Cancelled = 8,
+    Revealed = 9,
+    Drawn = 10,
+    GaveUp = 11,
+    GuessesAdjusted = 12,
+}
+
+/// A published game event as kept in the recent-events buffer.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EventRecord {
+    pub kind: EventKind,
+    pub session_id: u32,
+    pub ledger: u32,
+}
+
+/// How many games are in each phase. Games that expire from storage without
+/// finishing are not subtracted.
+#[contracttype]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PhaseCounts {
+    pub waiting_for_accept: u32,
+    pub waiting_for_commitment: u32,
+    pub waiting_for_guess: u32,
+    pub waiting_for_feedback: u32,
+    pub finished: u32,
+}
+
+/// A codemaker's feedback track record. `honesty` is clean reveals minus
+/// caught lies; a reveal later shown false by `dispute_feedback` no longer
+/// counts as clean.
+#[contracttype]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PlayerStats {
+    pub clean_reveals: u32,
+    pub caught_lies: u32,
+    pub honesty: i32,
+}
+
+/// Operational flags, for maintenance banners and feature gating.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ContractStatus {
+    pub paused: bool,
+    pub sealed: bool,
+    /// New games and tournaments can be created.
+    pub accepting_games: bool,
+    pub version: u32,
+}
+
+/// Timing policy shared by every timeout-based entrypoint, in ledgers.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TimeoutConfig {
+    pub move_timeout: u32,
+    pub feedback_timeout: u32,
+    pub abandonment_window: u32,
+    /// How long an invitation stays open before the codemaker may cancel it.
+    pub accept_window: u32,
+    /// How long after a game finishes its feedback can still be disputed;
+    /// the result is not reported to the hub until this has passed.
+    pub dispute_window: u32,
+    /// Extra time on top of the usual deadline for a game's first move: the
+    /// codemaker's commitment, and the codebreaker's first answered guess
+    /// before `cancel_stale` may clean the game up. Later moves get none.
+    pub first_move_grace: u32,
 }
 
 #[contracttype]
@@ -86,14 +495,246 @@ pub enum DataKey {
     GameHubAddress,
     VerifierAddress,
     Admin,
+    TimeoutConfig,
+    PlayerGames(Address),
+    GameNamespace,
+    Escrow(Address),
+    Migrated,
+    Tournament(u32),
+    TournamentCount,
+    DualGame(u32),
+    DualGameCount,
+    NextMatchId,
+    Sealed,
+    RatingsAddress,
+    Observer,
+    ObserverFailed,
+    EscrowTokens,
+    MaxGamesPerPlayer,
+    /// Circuit-specific verifier for `(code_length, num_colors)`.
+    VerifierFor(u32, u32),
+    /// Session ids of unfinished games, oldest first.
+    ActiveGames,
+    /// Session a codemaker created under a client-chosen idempotency key.
+    IdemKey(Address, BytesN<32>),
+    /// Merkle mountain range over every finished game's `result_hash`: its
+    /// bagged root, its peaks (tallest first) and its leaf count.
+    HistoryRoot,
+    HistoryPeaks,
+    HistoryCount,
+    Paused,
+    /// Ring buffer of the most recent game events, oldest first.
+    EventLog,
+    /// UltraHonk verification key of the default feedback circuit.
+    VerificationKey,
+    /// Every phase a game has been in, oldest first. Kept alongside the game.
+    PhaseHistory(u32),
+    PhaseCounts,
+    Treasury,
+    ResignPenaltyBps,
+    /// Tokens games may be staked in; empty allows any token.
+    AllowedTokens,
+    PlayerStats(Address),
+}
+
+// ============================================================================
+// Events
+// ============================================================================
+
+/// A guess was submitted. Guesses are answered in submission order.
+#[contractevent(topics = ["game", "guess"], data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GuessSubmitted {
+    #[topic]
+    pub session_id: u32,
+    pub guess: Vec<u32>,
+}
+
+/// Feedback on the oldest pending guess, with the phase the game moved to.
+#[contractevent(topics = ["game", "feedback"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeedbackGiven {
+    #[topic]
+    pub session_id: u32,
+    pub correct_position: u32,
+    pub correct_color: u32,
+    pub proof_hash: BytesN<32>,
+    pub phase: GamePhase,
+}
+
+/// The codemaker disclosed one peg of the secret.
+#[contractevent(topics = ["game", "hint"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HintGiven {
+    #[topic]
+    pub session_id: u32,
+    pub position: u32,
+    pub color: u32,
+}
+
+/// Both players agreed to raise the guess limit.
+#[contractevent(topics = ["game", "max_guesses"], data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MaxGuessesAdjusted {
+    #[topic]
+    pub session_id: u32,
+    pub max_guesses: u32,
+}
+
+/// The codebreaker has a single guess left.
+#[contractevent(topics = ["game", "last_chance"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LastChance {
+    #[topic]
+    pub session_id: u32,
+}
+
+/// A strict game went to the codebreaker because the codemaker's proof was
+/// rejected or the reveal exposed a false feedback.
+#[contractevent(topics = ["game", "forfeited"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GameForfeited {
+    #[topic]
+    pub session_id: u32,
+    pub winner: Address,
+    pub loser: Address,
+}
+
+/// The codemaker missed the feedback deadline and lost the game.
+#[contractevent(topics = ["game", "timed_out"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GameTimedOut {
+    #[topic]
+    pub session_id: u32,
+    pub winner: Address,
+    pub loser: Address,
+}
+
+/// The codebreaker cracked the code.
+#[contractevent(topics = ["game", "solved"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GameSolved {
+    #[topic]
+    pub session_id: u32,
+    pub winner: Address,
+    pub loser: Address,
+}
+
+/// The codebreaker ran out of guesses.
+#[contractevent(topics = ["game", "exhausted"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GameExhausted {
+    #[topic]
+    pub session_id: u32,
+    pub winner: Address,
+    pub loser: Address,
+}
+
+/// The game was called off before any play; stakes were refunded.
+#[contractevent(topics = ["game", "cancelled"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GameCancelled {
+    #[topic]
+    pub session_id: u32,
+    pub codemaker: Address,
+    pub codebreaker: Address,
+}
+
+/// The codemaker opened their commitment after the game.
+#[contractevent(topics = ["game", "revealed"], data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CodeRevealed {
+    #[topic]
+    pub session_id: u32,
+    pub secret: Vec<u32>,
+}
+
+/// The game ended without a winner.
+#[contractevent(topics = ["game", "drawn"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GameDrawn {
+    #[topic]
+    pub session_id: u32,
+    pub codemaker: Address,
+    pub codebreaker: Address,
+}
+
+/// The codebreaker conceded before cracking the code.
+#[contractevent(topics = ["game", "gave_up"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GameGivenUp {
+    #[topic]
+    pub session_id: u32,
+    pub winner: Address,
+    pub loser: Address,
+}
+
+/// A tournament round was paired up.
+#[contractevent(topics = ["tournament", "round"], data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TournamentRound {
+    #[topic]
+    pub tournament_id: u32,
+    pub round: u32,
+    pub matches: Vec<u32>,
+}
+
+/// The tournament final was decided.
+#[contractevent(topics = ["tournament", "won"], data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TournamentWon {
+    #[topic]
+    pub tournament_id: u32,
+    pub champion: Address,
+}
+
+/// A dual game was decided; no winner means a draw.
+#[contractevent(topics = ["dual", "decided"], data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DualGameDecided {
+    #[topic]
+    pub dual_id: u32,
+    pub winner: Option<Address>,
 }
 
 // ============================================================================
 // Constants
 // ============================================================================
 
+const CONTRACT_VERSION: u32 = 1;
 const GAME_TTL_LEDGERS: u32 = 518_400; // ~30 days
+const PERSISTENT_GAME_TTL_LEDGERS: u32 = 3_110_400; // ~180 days
 const MAX_GUESSES: u32 = 12;
+const CODE_LENGTH: u32 = 4;
+const NUM_COLORS: u32 = 6;
+const MAX_CODE_LENGTH: u32 = 8;
+const MAX_NUM_COLORS: u32 = 10;
+const MAX_GUESSES_LIMIT: u32 = 20;
+const MAX_PIPELINE_DEPTH: u32 = 4;
+const NAMESPACE_SHIFT: u32 = 24; // hub id = namespace << 24 | session_id
+const MAX_NAMESPACE: u32 = 0xFF;
+const MAX_NAMESPACED_SESSION_ID: u32 = 0x00FF_FFFF;
+const MAX_HINTS: u32 = 2;
+pub const FEEDBACK_SPLIT: u32 = 0;
+pub const FEEDBACK_COMBINED: u32 = 1;
+const SETTLE_BATCH_LIMIT: u32 = 20;
+const SEED_BATCH_LIMIT: u32 = 16;
+const SUGGEST_SEARCH_LIMIT: u32 = 1_296; // every code of the default 4-peg, 6-color game
+const SUGGEST_MINIMAX_LIMIT: usize = 32; // consistent codes scored by minimax
+const PLAYER_INDEX_LIMIT: u32 = 50; // most recent sessions kept per player
+const ACTIVE_INDEX_LIMIT: u32 = 200; // most recent unfinished games kept
+const PHASE_QUERY_LIMIT: u32 = 50;
+const EVENT_LOG_LIMIT: u32 = 100;
+// A guess and a feedback per turn, plus setup and finish
+const PHASE_HISTORY_LIMIT: u32 = 2 * MAX_GUESSES_LIMIT + 4;
+const MAX_ENCRYPTED_SECRET_LEN: u32 = 256;
+const MAX_DECRYPTION_KEY_LEN: u32 = 64;
+const DEFAULT_MAX_GAMES_PER_PLAYER: u32 = 10;
+const BPS_DENOMINATOR: u32 = 10_000;
+const DEFAULT_MOVE_TIMEOUT: u32 = 17_280; // ~1 day
+const DEFAULT_FEEDBACK_TIMEOUT: u32 = 17_280; // ~1 day
+const DEFAULT_ABANDONMENT_WINDOW: u32 = 120_960; // ~7 days
+const DEFAULT_ACCEPT_WINDOW: u32 = 17_280; // ~1 day
 
 // ============================================================================
 // Contract
@@ -107,7 +748,16 @@ impl ZKMindContract {
     /// Initialize the contract with admin and Game Hub address.
     /// verifier_address is stored for future on-chain ZK verification
     /// when Soroban budget limits support UltraHonk verification.
-    pub fn __constructor(env: Env, admin: Address, game_hub: Address, verifier: Address) {
+    /// game_namespace (0-255) separates this deployment's session ids from
+    /// other ZKMind deployments reporting to the same Game Hub.
+    pub fn __constructor(
+        env: Env,
+        admin: Address,
+        game_hub: Address,
+        verifier: Address,
+        game_namespace: u32,
+    ) {
+        assert!(game_namespace <= MAX_NAMESPACE, "Namespace out of range");
         env.storage().instance().set(&DataKey::Admin, &admin);
         env.storage()
             .instance()
@@ -115,287 +765,4114 @@ impl ZKMindContract {
         env.storage()
             .instance()
             .set(&DataKey::VerifierAddress, &verifier);
+        env.storage()
+            .instance()
+            .set(&DataKey::TimeoutConfig, &default_timeout_config());
+        env.storage()
+            .instance()
+            .set(&DataKey::GameNamespace, &game_namespace);
     }
 
-    /// Start a new game session. Both players must authorize.
+    /// Start a new game session. Only the codemaker authorizes here; the
+    /// codebreaker must opt in via `accept_game` before the code is committed.
     pub fn new_game(
         env: Env,
         session_id: u32,
         codemaker: Address,
         codebreaker: Address,
+    ) -> Result<(), Error> {
+        let config = default_config(&env);
+        Self::new_game_with_config(env, session_id, codemaker, codebreaker, config)
+    }
+
+    /// Start a new game session with custom rules. Same handshake as `new_game`.
+    /// In a staked game the codemaker escrows `codemaker_stake` here and the
+    /// codebreaker escrows `codebreaker_stake` on `accept_game`.
+    pub fn new_game_with_config(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        codebreaker: Address,
+        config: GameConfig,
     ) -> Result<(), Error> {
         codemaker.require_auth();
-        codebreaker.require_auth();
+        check_accepting_games(&env)?;
 
-        let game = GameState {
-            session_id,
-            codemaker: codemaker.clone(),
-            codebreaker: codebreaker.clone(),
-            phase: GamePhase::WaitingForCommitment,
-            commitment: BytesN::from_array(&env, &[0u8; 32]),
-            guesses: Vec::new(&env),
-            feedbacks: Vec::new(&env),
-            guess_count: 0,
-            max_guesses: MAX_GUESSES,
-            winner: None,
-            current_guess: Vec::new(&env),
-        };
+        validate_config(&config)?;
+        check_token_allowed(&env, &config.stake_token)?;
+        check_session_id(&env, session_id)?;
+        check_session_free(&env, session_id)?;
+        check_game_limit(&env, &codemaker)?;
+        check_game_limit(&env, &codebreaker)?;
+        let game = new_game_state(&env, session_id, codemaker, codebreaker, &config);
 
         let key = DataKey::Game(session_id);
-        env.storage().temporary().set(&key, &game);
-        env.storage()
-            .temporary()
-            .extend_ttl(&key, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+        save_game(&env, &key, &game);
+        index_game(&env, &game);
+        deposit_stake(&env, &game, &game.codemaker);
+        notify_observer(&env, &game);
 
         Ok(())
     }
 
-    /// CodeMaker commits their secret code hash (pedersen_hash).
-    /// The commitment is computed client-side using pedersen_hash([c0,c1,c2,c3]).
-    pub fn commit_code(
+    /// `new_game_with_config` that is safe to retry: the first call with a
+    /// given `idempotency_key` creates the game, later calls return the
+    /// session id it created without touching it. Keys are scoped to the
+    /// codemaker, so another player reusing a key gets a game of their own.
+    /// Keys should be random per game and are kept as long as a game would be.
+    pub fn new_game_idempotent(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        codebreaker: Address,
+        config: GameConfig,
+        idempotency_key: BytesN<32>,
+    ) -> Result<u32, Error> {
+        let idem_key = DataKey::IdemKey(codemaker.clone(), idempotency_key);
+        if let Some(existing) = env.storage().temporary().get(&idem_key) {
+            return Ok(existing);
+        }
+
+        Self::new_game_with_config(env.clone(), session_id, codemaker, codebreaker, config)?;
+        env.storage().temporary().set(&idem_key, &session_id);
+        env.storage()
+            .temporary()
+            .extend_ttl(&idem_key, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+        Ok(session_id)
+    }
+
+    /// Create a game and commit the secret in one call. Both players authorize,
+    /// so the accept handshake is implied and play starts at `WaitingForGuess`.
+    pub fn new_game_and_commit(
         env: Env,
         session_id: u32,
         codemaker: Address,
+        codebreaker: Address,
         commitment: BytesN<32>,
     ) -> Result<(), Error> {
         codemaker.require_auth();
+        codebreaker.require_auth();
+        check_accepting_games(&env)?;
+        check_session_id(&env, session_id)?;
+        check_session_free(&env, session_id)?;
+        check_game_limit(&env, &codemaker)?;
+        check_game_limit(&env, &codebreaker)?;
+
+        let mut game = new_game_state(
+            &env,
+            session_id,
+            codemaker,
+            codebreaker,
+            &default_config(&env),
+        );
+        game.commitment = commitment;
+        set_phase(&env, &mut game, GamePhase::WaitingForGuess);
 
         let key = DataKey::Game(session_id);
-        let mut game: GameState = env
-            .storage()
-            .temporary()
-            .get(&key)
-            .ok_or(Error::GameNotFound)?;
+        save_game(&env, &key, &game);
+        index_game(&env, &game);
+        register_with_hub(&env, &game);
+        notify_observer(&env, &game);
 
-        if game.phase != GamePhase::WaitingForCommitment {
-            return Err(Error::InvalidPhase);
-        }
-        if game.codemaker != codemaker {
-            return Err(Error::NotCodeMaker);
-        }
+        Ok(())
+    }
 
-        game.commitment = commitment;
-        game.phase = GamePhase::WaitingForGuess;
+    /// Solo practice: the contract is the codemaker and answers every guess
+    /// itself. The secret is drawn from the PRNG and kept in plaintext in
+    /// `secret`, where anyone can read it, so practice games carry no stakes
+    /// and are never reported to the hub.
+    pub fn new_practice_game(env: Env, session_id: u32, player: Address) -> Result<(), Error> {
+        player.require_auth();
+        check_accepting_games(&env)?;
+        check_session_id(&env, session_id)?;
+        check_session_free(&env, session_id)?;
+        check_game_limit(&env, &player)?;
 
-        env.storage().temporary().set(&key, &game);
-        env.storage()
-            .temporary()
-            .extend_ttl(&key, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+        let config = GameConfig {
+            trusted: true,
+            ..default_config(&env)
+        };
+        let mut game = new_game_state(
+            &env,
+            session_id,
+            env.current_contract_address(),
+            player.clone(),
+            &config,
+        );
+        let seed: BytesN<32> = env.prng().gen();
+        game.secret = seeded_secret(&env, &game, &seed, &randomness_anchor(&env, session_id));
+        let salt = BytesN::from_array(&env, &[0u8; 32]);
+        game.commitment = commitment_for(&env, game.commit_scheme, &game.secret, &salt);
+        set_phase(&env, &mut game, GamePhase::WaitingForGuess);
+
+        let key = DataKey::Game(session_id);
+        save_game(&env, &key, &game);
+        index_player_game(&env, &player, session_id);
+        index_active_game(&env, session_id);
+        notify_observer(&env, &game);
 
         Ok(())
     }
 
-    /// CodeBreaker submits a guess (4 colors, values 0-5).
-    pub fn submit_guess(
-        env: Env,
-        session_id: u32,
-        codebreaker: Address,
-        guess: Vec<u32>,
-    ) -> Result<(), Error> {
+    /// CodeBreaker accepts the invitation, moving the game to commitment.
+    /// The game is registered with the Game Hub once both players are in.
+    pub fn accept_game(env: Env, session_id: u32, codebreaker: Address) -> Result<(), Error> {
         codebreaker.require_auth();
 
         let key = DataKey::Game(session_id);
-        let mut game: GameState = env
-            .storage()
-            .temporary()
-            .get(&key)
-            .ok_or(Error::GameNotFound)?;
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
 
-        if game.phase != GamePhase::WaitingForGuess {
-            return Err(Error::InvalidPhase);
-        }
+        assert_phase(&game, GamePhase::WaitingForAccept)?;
         if game.codebreaker != codebreaker {
             return Err(Error::NotCodeBreaker);
         }
 
-        if guess.len() != 4 {
-            return Err(Error::InvalidGuessValue);
-        }
-        for i in 0..4 {
-            let val = guess.get(i).unwrap();
-            if val > 5 {
-                return Err(Error::InvalidGuessValue);
-            }
+        // A codemaker who committed early via `codemaker_ready` is not waited on
+        if game.commitment == BytesN::from_array(&env, &[0u8; 32]) {
+            set_phase(&env, &mut game, GamePhase::WaitingForCommitment);
+        } else {
+            set_phase(&env, &mut game, GamePhase::WaitingForGuess);
         }
 
-        game.current_guess = guess;
-        game.phase = GamePhase::WaitingForFeedback;
-
-        env.storage().temporary().set(&key, &game);
-        env.storage()
-            .temporary()
-            .extend_ttl(&key, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+        save_game(&env, &key, &game);
+        register_with_hub(&env, &game);
+        deposit_stake(&env, &game, &game.codebreaker);
+        notify_observer(&env, &game);
 
         Ok(())
     }
 
-    /// CodeMaker submits feedback with a ZK proof hash.
-    ///
-    /// The ZK proof is generated and verified client-side using Noir + bb:
-    /// - Circuit proves: feedback is correct for the committed secret code
-    /// - Proof is verified by the opponent's client before accepting
-    /// - proof_hash = sha256(proof_bytes) stored on-chain for auditability
-    ///
-    /// Architecture note: Full on-chain UltraHonk verification is deployed
-    /// at the verifier_address but currently exceeds Soroban budget limits
-    /// for circuits of this size. When budget limits are raised, this contract
-    /// can be upgraded to call verify_proof on-chain.
-    pub fn submit_feedback(
+    /// CodeMaker commits their secret code hash: `H(secret || salt)` with the
+    /// game's `commit_scheme` (sha256 or keccak256), each peg a big-endian
+    /// u32, exactly as `reveal_code` recomputes it. `compute_commitment`
+    /// returns the expected value.
+    pub fn commit_code(
         env: Env,
         session_id: u32,
         codemaker: Address,
-        correct_position: u32,
-        correct_color: u32,
-        proof_hash: BytesN<32>,
+        commitment: BytesN<32>,
     ) -> Result<(), Error> {
         codemaker.require_auth();
 
         let key = DataKey::Game(session_id);
-        let mut game: GameState = env
-            .storage()
-            .temporary()
-            .get(&key)
-            .ok_or(Error::GameNotFound)?;
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
 
-        if game.phase != GamePhase::WaitingForFeedback {
-            return Err(Error::InvalidPhase);
+        assert_phase(&game, GamePhase::WaitingForCommitment)?;
+        if game.codemaker != codemaker {
+            return Err(Error::NotCodeMaker);
         }
+        if game.validity_proof {
+            return Err(Error::MissingProof);
+        }
+
+        game.commitment = commitment;
+        set_phase(&env, &mut game, GamePhase::WaitingForGuess);
+
+        save_game(&env, &key, &game);
+        notify_observer(&env, &game);
+
+        Ok(())
+    }
+
+    /// CodeMaker commits without waiting for the codebreaker to accept. Their
+    /// stake is already in escrow from game creation, so once the codebreaker
+    /// accepts, play starts at `WaitingForGuess`. Also works after accepting,
+    /// like `commit_code`.
+    pub fn codemaker_ready(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        commitment: BytesN<32>,
+    ) -> Result<(), Error> {
+        codemaker.require_auth();
+
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        assert_phase_in(
+            &game,
+            &[GamePhase::WaitingForAccept, GamePhase::WaitingForCommitment],
+        )?;
         if game.codemaker != codemaker {
             return Err(Error::NotCodeMaker);
         }
+        if game.validity_proof {
+            return Err(Error::MissingProof);
+        }
+        let unset = BytesN::from_array(&env, &[0u8; 32]);
+        if game.commitment != unset {
+            return Err(Error::AlreadyCommitted);
+        }
+        if commitment == unset {
+            return Err(Error::InvalidConfig);
+        }
 
-        // Validate feedback bounds
-        if correct_position > 4 || correct_color > 4 || correct_position + correct_color > 4 {
-            return Err(Error::InvalidFeedback);
+        game.commitment = commitment;
+        if game.phase == GamePhase::WaitingForCommitment {
+            set_phase(&env, &mut game, GamePhase::WaitingForGuess);
         }
 
-        let feedback = Feedback {
-            correct_position,
-            correct_color,
-            proof_hash,
-        };
+        save_game(&env, &key, &game);
+        notify_observer(&env, &game);
 
-        game.guesses.push_back(game.current_guess.clone());
-        game.feedbacks.push_back(feedback);
-        game.guess_count += 1;
-
-        // Check win: all 4 correct positions
-        if correct_position == 4 {
-            game.phase = GamePhase::Finished;
-            game.winner = Some(game.codebreaker.clone());
-        } else if game.guess_count >= game.max_guesses {
-            game.phase = GamePhase::Finished;
-            game.winner = Some(game.codemaker.clone());
-        } else {
-            game.phase = GamePhase::WaitingForGuess;
+        Ok(())
+    }
+
+    /// CodeMaker commits their secret together with the hash of a proof that
+    /// it is well-formed, independent of any guess. Required in games created
+    /// with `validity_proof`, allowed in any other.
+    pub fn commit_code_with_validity_proof(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        commitment: BytesN<32>,
+        proof_hash: BytesN<32>,
+    ) -> Result<(), Error> {
+        codemaker.require_auth();
+
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        assert_phase(&game, GamePhase::WaitingForCommitment)?;
+        if game.codemaker != codemaker {
+            return Err(Error::NotCodeMaker);
+        }
+        if proof_hash == BytesN::from_array(&env, &[0u8; 32]) {
+            return Err(Error::MissingProof);
         }
 
-        env.storage().temporary().set(&key, &game);
-        env.storage()
-            .temporary()
-            .extend_ttl(&key, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+        game.commitment = commitment;
+        game.fair_play.validity_proof_hash = Some(proof_hash);
+        set_phase(&env, &mut game, GamePhase::WaitingForGuess);
 
-        // Game Hub reporting is done via separate report_result call
+        save_game(&env, &key, &game);
+        notify_observer(&env, &game);
 
         Ok(())
     }
 
-    /// Get the current game state (read-only).
-    pub fn get_game(env: Env, session_id: u32) -> Result<GameState, Error> {
+    /// CodeMaker commits their secret and also publishes it encrypted, so the
+    /// game can be audited after `decrypt_key_reveal` without any proof.
+    /// The blob is stored as given, up to `MAX_ENCRYPTED_SECRET_LEN` bytes.
+    pub fn commit_code_encrypted(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        commitment: BytesN<32>,
+        encrypted_secret: Bytes,
+    ) -> Result<(), Error> {
+        codemaker.require_auth();
+
         let key = DataKey::Game(session_id);
-        env.storage()
-            .temporary()
-            .get(&key)
-            .ok_or(Error::GameNotFound)
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        assert_phase(&game, GamePhase::WaitingForCommitment)?;
+        if game.codemaker != codemaker {
+            return Err(Error::NotCodeMaker);
+        }
+        if game.validity_proof {
+            return Err(Error::MissingProof);
+        }
+        if encrypted_secret.is_empty() || encrypted_secret.len() > MAX_ENCRYPTED_SECRET_LEN {
+            return Err(Error::BlobTooLarge);
+        }
+
+        game.commitment = commitment;
+        game.fair_play.encrypted_secret = Some(encrypted_secret);
+        set_phase(&env, &mut game, GamePhase::WaitingForGuess);
+
+        save_game(&env, &key, &game);
+        notify_observer(&env, &game);
+
+        Ok(())
     }
 
-    /// Get the verifier contract address (for client-side reference).
-    pub fn get_verifier(env: Env) -> Address {
-        env.storage()
-            .instance()
-            .get(&DataKey::VerifierAddress)
-            .expect("Verifier not set")
+    /// The commitment `reveal_code` expects for `secret` and `salt` under
+    /// `scheme`, so clients commit with the contract's own hashing instead of
+    /// reimplementing it.
+    pub fn compute_commitment(
+        env: Env,
+        scheme: CommitScheme,
+        secret: Vec<u32>,
+        salt: BytesN<32>,
+    ) -> BytesN<32> {
+        commitment_for(&env, scheme, &secret, &salt)
     }
 
-    /// Report game result to Game Hub. Can be called by either player after game ends.
-    pub fn report_result(env: Env, session_id: u32) -> Result<(), Error> {
+    /// Provably-fair mode: before committing, the codemaker fixes
+    /// `seed_hash = sha256(seed)`. The secret is then derived from the seed
+    /// and `seed_entropy`, and committed with `commit_code` as usual. The
+    /// entropy mixes the ledger anchor with the PRNG output of the call
+    /// itself, which only exists once the network applies it, so the
+    /// codemaker cannot grind seeds against it.
+    pub fn commit_seed(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        seed_hash: BytesN<32>,
+    ) -> Result<(), Error> {
+        codemaker.require_auth();
+
         let key = DataKey::Game(session_id);
-        let game: GameState = env
-            .storage()
-            .temporary()
-            .get(&key)
-            .ok_or(Error::GameNotFound)?;
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
 
-        if game.phase != GamePhase::Finished {
+        assert_phase(&game, GamePhase::WaitingForCommitment)?;
+        if game.codemaker != codemaker {
+            return Err(Error::NotCodeMaker);
+        }
+        // A second seed would let the codemaker shop for entropy
+        if game.fair_play.seed_hash.is_some() {
             return Err(Error::InvalidPhase);
         }
 
-        let game_hub_addr: Address = env
-            .storage()
-            .instance()
-            .get(&DataKey::GameHubAddress)
-            .expect("GameHub not set");
-        let game_hub = GameHubClient::new(&env, &game_hub_addr);
-        let codemaker_won = game.winner == Some(game.codemaker.clone());
-        game_hub.end_game(&session_id, &codemaker_won);
+        game.fair_play.seed_hash = Some(seed_hash);
+        let mut entropy = Bytes::from_array(&env, &randomness_anchor(&env, session_id).to_array());
+        let draw: BytesN<32> = env.prng().gen();
+        entropy.extend_from_array(&draw.to_array());
+        game.fair_play.seed_entropy = Some(env.crypto().sha256(&entropy).into());
+
+        save_game(&env, &key, &game);
+        notify_observer(&env, &game);
 
         Ok(())
     }
 
-    // ========================================================================
-    // Admin Functions
-    // ========================================================================
+    /// CodeBreaker cancels a game whose codemaker never committed within
+    /// `move_timeout` plus `first_move_grace` ledgers. No gameplay happened,
+    /// so nobody wins: stakes are refunded and the game is deleted.
+    pub fn claim_commit_timeout(
+        env: Env,
+        session_id: u32,
+        codebreaker: Address,
+    ) -> Result<(), Error> {
+        codebreaker.require_auth();
 
-    pub fn get_admin(env: Env) -> Address {
-        env.storage()
-            .instance()
-            .get(&DataKey::Admin)
-            .expect("Admin not set")
+        let key = DataKey::Game(session_id);
+        let game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        assert_phase(&game, GamePhase::WaitingForCommitment)?;
+        if game.codebreaker != codebreaker {
+            return Err(Error::NotCodeBreaker);
+        }
+        if env.ledger().sequence() <= timeout_deadline(&env, &game).unwrap() {
+            return Err(Error::TimeoutNotReached);
+        }
+
+        cancel_game(&env, &key, game);
+
+        Ok(())
     }
 
-    pub fn set_admin(env: Env, new_admin: Address) {
-        let admin: Address = env
-            .storage()
-            .instance()
-            .get(&DataKey::Admin)
-            .expect("Admin not set");
-        admin.require_auth();
-        env.storage().instance().set(&DataKey::Admin, &new_admin);
+    /// CodeMaker withdraws an invitation the codebreaker has not accepted
+    /// within `accept_window` ledgers. The codemaker's stake is refunded and
+    /// the game is deleted.
+    pub fn claim_accept_timeout(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+    ) -> Result<(), Error> {
+        codemaker.require_auth();
+
+        let key = DataKey::Game(session_id);
+        let game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        assert_phase(&game, GamePhase::WaitingForAccept)?;
+        if game.codemaker != codemaker {
+            return Err(Error::NotCodeMaker);
+        }
+        if env.ledger().sequence() <= timeout_deadline(&env, &game).unwrap() {
+            return Err(Error::TimeoutNotReached);
+        }
+
+        cancel_game(&env, &key, game);
+
+        Ok(())
     }
 
-    pub fn set_verifier(env: Env, new_verifier: Address) {
-        let admin: Address = env
-            .storage()
-            .instance()
-            .get(&DataKey::Admin)
-            .expect("Admin not set");
-        admin.require_auth();
-        env.storage()
-            .instance()
-            .set(&DataKey::VerifierAddress, &new_verifier);
+    /// CodeBreaker lets `agent` (e.g. a solver bot) submit guesses for them.
+    /// Replaces any previous agent.
+    pub fn authorize_guess_agent(
+        env: Env,
+        session_id: u32,
+        codebreaker: Address,
+        agent: Address,
+    ) -> Result<(), Error> {
+        codebreaker.require_auth();
+
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        if game.phase == GamePhase::Finished {
+            return Err(Error::GameAlreadyEnded);
+        }
+        if game.codebreaker != codebreaker {
+            return Err(Error::NotCodeBreaker);
+        }
+
+        game.guess_agent = Some(agent);
+        save_game(&env, &key, &game);
+        notify_observer(&env, &game);
+
+        Ok(())
     }
 
-    pub fn set_hub(env: Env, new_hub: Address) {
-        let admin: Address = env
-            .storage()
-            .instance()
-            .get(&DataKey::Admin)
-            .expect("Admin not set");
-        admin.require_auth();
-        env.storage()
-            .instance()
-            .set(&DataKey::GameHubAddress, &new_hub);
+    /// CodeBreaker, or their guess agent, submits a guess (`code_length`
+    /// colors, values below `num_colors`). When `pipeline_depth > 1`, up to
+    /// that many guesses may await feedback.
+    pub fn submit_guess(
+        env: Env,
+        session_id: u32,
+        caller: Address,
+        guess: Vec<u32>,
+    ) -> Result<(), Error> {
+        caller.require_auth();
+
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        // With pipelining, further guesses may queue behind unanswered ones
+        let can_queue = game.phase == GamePhase::WaitingForFeedback
+            && game.pending_guesses.len() < game.pipeline_depth;
+        if !can_queue {
+            assert_phase(&game, GamePhase::WaitingForGuess)?;
+        }
+        if game.codebreaker != caller && game.guess_agent != Some(caller) {
+            return Err(Error::NotCodeBreaker);
+        }
+        if game.guess_count + game.pending_guesses.len() >= guess_limit(&game) {
+            return Err(Error::MaxGuessesReached);
+        }
+
+        validate_guess(&game, &guess)?;
+
+        game.pending_guesses.push_back(guess.clone());
+        game.current_guess = game.pending_guesses.first().unwrap();
+        set_phase(&env, &mut game, GamePhase::WaitingForFeedback);
+
+        save_game(&env, &key, &game);
+        GuessSubmitted { session_id, guess }.publish(&env);
+        log_event(&env, EventKind::Guess, session_id);
+        notify_observer(&env, &game);
+
+        if is_practice(&env, &game) {
+            let (correct_position, correct_color) = practice_feedback(&game);
+            let exact = game.pending_guesses.first_unchecked() == game.secret;
+            return record_feedback(
+                &env,
+                session_id,
+                game.codemaker,
+                correct_position,
+                correct_color,
+                exact,
+                BytesN::from_array(&env, &[0u8; 32]),
+            );
+        }
+
+        Ok(())
     }
 
-    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) {
-        let admin: Address = env
-            .storage()
-            .instance()
-            .get(&DataKey::Admin)
-            .expect("Admin not set");
-        admin.require_auth();
-        env.deployer().update_current_contract_wasm(new_wasm_hash);
+    /// CodeMaker submits feedback with a ZK proof hash.
+    ///
+    /// The ZK proof is generated and verified client-side using Noir + bb:
+    /// - Circuit proves: feedback is correct for the committed secret code
+    /// - Proof is verified by the opponent's client before accepting
+    /// - proof_hash = sha256(proof_bytes) stored on-chain for auditability
+    ///
+    /// Architecture note: Full on-chain UltraHonk verification is deployed
+    /// at the verifier_address but currently exceeds Soroban budget limits
+    /// for circuits of this size. When budget limits are raised, this contract
+    /// can be upgraded to call verify_proof on-chain.
+    pub fn submit_feedback(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        correct_position: u32,
+        correct_color: u32,
+        proof_hash: BytesN<32>,
+    ) -> Result<(), Error> {
+        codemaker.require_auth();
+
+        record_feedback(
+            &env,
+            session_id,
+            codemaker,
+            correct_position,
+            correct_color,
+            false,
+            proof_hash,
+        )
     }
+
+    /// `submit_feedback` that also names the guess the proof was computed
+    /// for: `guess_index` counts guesses from 0 and must be the oldest
+    /// pending one, so a proof for an earlier or later guess is refused.
+    pub fn submit_feedback_for_guess(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        guess_index: u32,
+        correct_position: u32,
+        correct_color: u32,
+        proof_hash: BytesN<32>,
+    ) -> Result<(), Error> {
+        codemaker.require_auth();
+
+        let game = Self::get_game(env.clone(), session_id)?;
+        assert_phase(&game, GamePhase::WaitingForFeedback)?;
+        if guess_index != game.guesses.len() {
+            return Err(Error::ProofIndexMismatch);
+        }
+
+        record_feedback(
+            &env,
+            session_id,
+            codemaker,
+            correct_position,
+            correct_color,
+            false,
+            proof_hash,
+        )
+    }
+
+    /// Feedback for a `FEEDBACK_COMBINED` game: `score` counts every guessed
+    /// peg of a right color, in the right place or not. It is stored as
+    /// `correct_position` with `correct_color = 0`. Any permutation of the
+    /// secret scores full, so only `solved`, which requires a full score,
+    /// ends the game as solved. A false `solved` either way is caught by a
+    /// strict reveal or `dispute_feedback`.
+    pub fn submit_score(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        score: u32,
+        solved: bool,
+        proof_hash: BytesN<32>,
+    ) -> Result<(), Error> {
+        codemaker.require_auth();
+
+        let game = Self::get_game(env.clone(), session_id)?;
+        if game.feedback_mode != FEEDBACK_COMBINED {
+            return Err(Error::InvalidFeedback);
+        }
+        record_feedback(&env, session_id, codemaker, score, 0, solved, proof_hash)
+    }
+
+    /// In a `sealed_feedback_allowed` game, commit to the feedback for the
+    /// oldest pending guess as `sha256(correct_position || correct_color ||
+    /// nonce)`, pegs as big-endian u32. The codemaker hands the opening to the
+    /// codebreaker off-chain (e.g. encrypted to them) so that, should the
+    /// codemaker then stall, the codebreaker can reveal it with
+    /// `open_sealed_feedback` and play on.
+    pub fn seal_feedback(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        sealed: BytesN<32>,
+    ) -> Result<(), Error> {
+        codemaker.require_auth();
+
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        assert_phase(&game, GamePhase::WaitingForFeedback)?;
+        if game.codemaker != codemaker {
+            return Err(Error::NotCodeMaker);
+        }
+        if !game.sealed_feedback_allowed {
+            return Err(Error::InvalidConfig);
+        }
+
+        game.fair_play.sealed_feedback = Some(sealed);
+        save_game(&env, &key, &game);
+        notify_observer(&env, &game);
+
+        Ok(())
+    }
+
+    /// CodeBreaker claims the game from a codemaker who left a guess without
+    /// feedback for `feedback_timeout` ledgers. If the codemaker sealed that
+    /// feedback, the codebreaker must first try `open_sealed_feedback`; the
+    /// win is only awarded once a second window has passed as well.
+    pub fn claim_feedback_timeout(
+        env: Env,
+        session_id: u32,
+        codebreaker: Address,
+    ) -> Result<(), Error> {
+        codebreaker.require_auth();
+
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        assert_phase(&game, GamePhase::WaitingForFeedback)?;
+        if game.codebreaker != codebreaker {
+            return Err(Error::NotCodeBreaker);
+        }
+        if env.ledger().sequence() <= timeout_deadline(&env, &game).unwrap() {
+            return Err(Error::TimeoutNotReached);
+        }
+
+        time_out_codemaker(&env, &key, &mut game);
+
+        Ok(())
+    }
+
+    /// Apply whatever deadline a game has passed, callable by anyone. A game
+    /// still uncommitted after `commit_deadline_ledgers`, or past the timeout
+    /// of its accept, commit or first-guess phase, is cancelled with stakes
+    /// refunded as in the matching claim. A feedback timeout awards the
+    /// codebreaker as in `claim_feedback_timeout`. Returns whether anything
+    /// happened.
+    pub fn poke(env: Env, session_id: u32) -> Result<bool, Error> {
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+        let now = env.ledger().sequence();
+
+        let uncommitted = matches!(
+            game.phase,
+            GamePhase::WaitingForAccept | GamePhase::WaitingForCommitment
+        ) && game.commitment == BytesN::from_array(&env, &[0u8; 32]);
+        if let Some(ledgers) = game.fair_play.commit_deadline_ledgers {
+            if uncommitted && now > game.created_ledger.saturating_add(ledgers) {
+                cancel_game(&env, &key, game);
+                return Ok(true);
+            }
+        }
+
+        match timeout_deadline(&env, &game) {
+            Some(deadline) if now > deadline => {
+                if game.phase == GamePhase::WaitingForFeedback {
+                    time_out_codemaker(&env, &key, &mut game);
+                } else {
+                    cancel_game(&env, &key, game);
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// CodeBreaker reveals the feedback the codemaker sealed but never
+    /// submitted, once `feedback_timeout` has passed. It is recorded as if
+    /// the codemaker had sent it, with the seal as its proof hash.
+    pub fn open_sealed_feedback(
+        env: Env,
+        session_id: u32,
+        codebreaker: Address,
+        correct_position: u32,
+        correct_color: u32,
+        nonce: BytesN<32>,
+    ) -> Result<(), Error> {
+        codebreaker.require_auth();
+
+        let game = Self::get_game(env.clone(), session_id)?;
+        assert_phase(&game, GamePhase::WaitingForFeedback)?;
+        if game.codebreaker != codebreaker {
+            return Err(Error::NotCodeBreaker);
+        }
+        let sealed = game
+            .fair_play
+            .sealed_feedback
+            .clone()
+            .ok_or(Error::InvalidPhase)?;
+        let deadline = game
+            .phase_entered_ledger
+            .saturating_add(timeout_config(&env).feedback_timeout);
+        if env.ledger().sequence() <= deadline {
+            return Err(Error::TimeoutNotReached);
+        }
+
+        let mut preimage = Bytes::new(&env);
+        preimage.extend_from_array(&correct_position.to_be_bytes());
+        preimage.extend_from_array(&correct_color.to_be_bytes());
+        preimage.extend_from_array(&nonce.to_array());
+        if BytesN::from(env.crypto().sha256(&preimage)) != sealed {
+            return Err(Error::CommitmentMismatch);
+        }
+
+        record_feedback(
+            &env,
+            session_id,
+            game.codemaker,
+            correct_position,
+            correct_color,
+            false,
+            sealed,
+        )
+    }
+
+    /// Submit feedback together with its proof, checked on-chain by the
+    /// verifier. The stored proof hash is `sha256(proof)`. A rejected proof
+    /// is refused, or in a `strict_proofs` game forfeits it to the codebreaker.
+    pub fn submit_proven_feedback(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        correct_position: u32,
+        correct_color: u32,
+        proof: Bytes,
+    ) -> Result<(), Error> {
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        assert_phase(&game, GamePhase::WaitingForFeedback)?;
+        if game.codemaker != codemaker {
+            return Err(Error::NotCodeMaker);
+        }
+
+        let public_inputs = feedback_public_inputs(&env, &game, correct_position, correct_color);
+        if verify_feedback_proof(&env, &game, &public_inputs, &proof) {
+            let proof_hash = env.crypto().sha256(&proof).into();
+            return Self::submit_feedback(
+                env,
+                session_id,
+                codemaker,
+                correct_position,
+                correct_color,
+                proof_hash,
+            );
+        }
+        if !game.strict_proofs {
+            return Err(Error::InvalidProof);
+        }
+
+        codemaker.require_auth();
+        let winner = game.codebreaker.clone();
+        finish_game(&env, &mut game, Some(winner.clone()), FinishReason::Forfeit);
+
+        save_game(&env, &key, &game);
+        GameForfeited {
+            session_id,
+            winner,
+            loser: codemaker,
+        }
+        .publish(&env);
+        log_event(&env, EventKind::Forfeited, session_id);
+        notify_observer(&env, &game);
+
+        Ok(())
+    }
+
+    /// Cancel every game in `player`'s index that has sat in the same phase
+    /// for longer than `abandonment_window` plus `first_move_grace` without a
+    /// single answered guess. A game waiting on feedback is left alone: the
+    /// codebreaker claims that one with `claim_feedback_timeout` instead.
+    /// Stakes are refunded as in `claim_commit_timeout`. Bounded by the
+    /// player index size. Returns the number of games cancelled.
+    pub fn cancel_stale(env: Env, player: Address) -> u32 {
+        player.require_auth();
+
+        let config = timeout_config(&env);
+        let window = config
+            .abandonment_window
+            .saturating_add(config.first_move_grace);
+        let mut cancelled = 0;
+        for session_id in player_games(&env, &player).iter() {
+            let key = DataKey::Game(session_id);
+            let Some(game) = load_game(&env, &key) else {
+                continue;
+            };
+            let stale = game.phase != GamePhase::Finished
+                && game.phase != GamePhase::WaitingForFeedback
+                && game.guess_count == 0
+                && env.ledger().sequence() > game.phase_entered_ledger.saturating_add(window);
+            if !stale || (game.codemaker != player && game.codebreaker != player) {
+                continue;
+            }
+
+            cancel_game(&env, &key, game);
+            cancelled += 1;
+        }
+        cancelled
+    }
+
+    /// Teaching mode: the codemaker discloses the color at one position.
+    /// Each hint costs the codebreaker a guess, at most `MAX_HINTS` per game,
+    /// and never the last remaining guess.
+    pub fn give_hint(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        position: u32,
+        color: u32,
+    ) -> Result<(), Error> {
+        codemaker.require_auth();
+
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        assert_phase(&game, GamePhase::WaitingForGuess)?;
+        if game.codemaker != codemaker {
+            return Err(Error::NotCodeMaker);
+        }
+        if !game.hints_allowed {
+            return Err(Error::HintsNotAllowed);
+        }
+        if game.hints.len() >= MAX_HINTS || game.guess_count + 1 >= guess_limit(&game) {
+            return Err(Error::HintLimitReached);
+        }
+        if position >= game.code_length || color >= game.num_colors {
+            return Err(Error::InvalidGuessValue);
+        }
+
+        game.hints.push_back(Hint { position, color });
+
+        save_game(&env, &key, &game);
+        HintGiven {
+            session_id,
+            position,
+            color,
+        }
+        .publish(&env);
+        log_event(&env, EventKind::Hint, session_id);
+        notify_observer(&env, &game);
+
+        Ok(())
+    }
+
+    /// Raise an unfinished game's guess limit, with both players' consent.
+    /// The limit can only go up, and not past `MAX_GUESSES_LIMIT`.
+    pub fn adjust_max_guesses(env: Env, session_id: u32, new_max: u32) -> Result<(), Error> {
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        game.codemaker.require_auth();
+        game.codebreaker.require_auth();
+
+        if game.phase == GamePhase::Finished {
+            return Err(Error::GameAlreadyEnded);
+        }
+        if new_max <= game.max_guesses || new_max > MAX_GUESSES_LIMIT {
+            return Err(Error::InvalidConfig);
+        }
+
+        game.max_guesses = new_max;
+
+        save_game(&env, &key, &game);
+        MaxGuessesAdjusted {
+            session_id,
+            max_guesses: new_max,
+        }
+        .publish(&env);
+        log_event(&env, EventKind::GuessesAdjusted, session_id);
+        notify_observer(&env, &game);
+
+        Ok(())
+    }
+
+    /// Either player moves the game from temporary to persistent storage, so
+    /// it is archived rather than deleted if its TTL lapses and can be
+    /// restored. Every later read and write of the game uses the persistent
+    /// copy. Other per-game records such as the phase history stay temporary.
+    pub fn persist_game(env: Env, session_id: u32, player: Address) -> Result<(), Error> {
+        player.require_auth();
+
+        let key = DataKey::Game(session_id);
+        let game = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+        if player != game.codemaker && player != game.codebreaker {
+            return Err(Error::NotPlayer);
+        }
+        if env.storage().persistent().has(&key) {
+            return Ok(());
+        }
+
+        env.storage().temporary().remove(&key);
+        env.storage().persistent().set(&key, &game);
+        env.storage().persistent().extend_ttl(
+            &key,
+            PERSISTENT_GAME_TTL_LEDGERS,
+            PERSISTENT_GAME_TTL_LEDGERS,
+        );
+
+        Ok(())
+    }
+
+    /// CodeBreaker concedes, awarding the game to the codemaker. Guesses and
+    /// feedbacks recorded so far become the final history; an unanswered
+    /// guess is discarded.
+    pub fn give_up(env: Env, session_id: u32, codebreaker: Address) -> Result<(), Error> {
+        codebreaker.require_auth();
+
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        if game.phase == GamePhase::Finished {
+            return Err(Error::GameAlreadyEnded);
+        }
+        assert_phase_in(
+            &game,
+            &[GamePhase::WaitingForGuess, GamePhase::WaitingForFeedback],
+        )?;
+        if game.codebreaker != codebreaker {
+            return Err(Error::NotCodeBreaker);
+        }
+
+        game.current_guess = Vec::new(&env);
+        let winner = game.codemaker.clone();
+        finish_game(
+            &env,
+            &mut game,
+            Some(winner.clone()),
+            FinishReason::Resigned,
+        );
+
+        save_game(&env, &key, &game);
+
+        GameGivenUp {
+            session_id,
+            winner,
+            loser: codebreaker,
+        }
+        .publish(&env);
+        log_event(&env, EventKind::GaveUp, session_id);
+        notify_observer(&env, &game);
+
+        Ok(())
+    }
+
+    /// CodeMaker opens their commitment once the game is over. The secret must
+    /// be well-formed for the game's rules before the commitment
+    /// `H(secret || salt)` is recomputed with the game's `commit_scheme`, so a
+    /// malformed secret can never pass as a reveal. Each peg is encoded as a
+    /// big-endian u32.
+    ///
+    /// In a `strict_proofs` game every recorded feedback is then recomputed
+    /// against the secret; if any was false the game is awarded to the
+    /// codebreaker with `FinishReason::FalseFeedback`. Stakes already paid
+    /// out and results already reported to the hub are not reversed.
+    ///
+    /// A game with a committed seed must be opened with `reveal_seed`, so the
+    /// secret is shown to come from the seed.
+    pub fn reveal_code(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        secret: Vec<u32>,
+        salt: BytesN<32>,
+    ) -> Result<(), Error> {
+        codemaker.require_auth();
+
+        let game = Self::get_game(env.clone(), session_id)?;
+        // A seeded secret must be shown to come from the seed
+        if game.fair_play.seed_hash.is_some() {
+            return Err(Error::InvalidReveal);
+        }
+        open_commitment(&env, session_id, codemaker, secret, salt)
+    }
+
+    /// Publishes the key for the secret encrypted at commit time. Only once
+    /// the game is over, and only once.
+    pub fn decrypt_key_reveal(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        decryption_key: Bytes,
+    ) -> Result<(), Error> {
+        codemaker.require_auth();
+
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        assert_phase(&game, GamePhase::Finished)?;
+        if game.codemaker != codemaker {
+            return Err(Error::NotCodeMaker);
+        }
+        if game.fair_play.encrypted_secret.is_none() {
+            return Err(Error::InvalidReveal);
+        }
+        if game.fair_play.decryption_key.is_some() {
+            return Err(Error::AlreadyRevealed);
+        }
+        if decryption_key.is_empty() || decryption_key.len() > MAX_DECRYPTION_KEY_LEN {
+            return Err(Error::BlobTooLarge);
+        }
+
+        game.fair_play.decryption_key = Some(decryption_key);
+
+        save_game(&env, &key, &game);
+        notify_observer(&env, &game);
+
+        Ok(())
+    }
+
+    /// During the dispute window, the codebreaker can show that the feedback
+    /// to guess `index` disagrees with the revealed secret. The game is then
+    /// awarded to them with `FinishReason::FalseFeedback`. A codemaker who
+    /// wins but never reveals cannot be disputed this way; see
+    /// `claim_reveal_timeout`.
+    pub fn dispute_feedback(
+        env: Env,
+        session_id: u32,
+        codebreaker: Address,
+        index: u32,
+    ) -> Result<(), Error> {
+        codebreaker.require_auth();
+
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        assert_phase(&game, GamePhase::Finished)?;
+        if game.codebreaker != codebreaker {
+            return Err(Error::NotCodeBreaker);
+        }
+        if !game.revealed
+            || !dispute_window_open(&env, &game)
+            || game.finish_reason == FinishReason::FalseFeedback
+        {
+            return Err(Error::InvalidPhase);
+        }
+
+        let guess = game.guesses.get(index).ok_or(Error::NoFeedbackYet)?;
+        let feedback = game.feedbacks.get(index).ok_or(Error::NoFeedbackYet)?;
+        let mut secret = [0u32; MAX_CODE_LENGTH as usize];
+        let mut code = [0u32; MAX_CODE_LENGTH as usize];
+        for (peg, color) in game.secret.iter().enumerate() {
+            secret[peg] = color;
+        }
+        for (peg, color) in guess.iter().enumerate() {
+            code[peg] = color;
+        }
+        let len = game.code_length as usize;
+        let expected = compute_feedback(
+            &secret[..len],
+            &code[..len],
+            game.num_colors,
+            game.feedback_mode,
+        );
+        let is_secret = code[..len] == secret[..len];
+        if expected == (feedback.correct_position, feedback.correct_color)
+            && solve_claim_holds(&game, index, is_secret)
+        {
+            return Err(Error::InvalidFeedback);
+        }
+
+        overturn_result(&env, &mut game, FinishReason::FalseFeedback);
+
+        save_game(&env, &key, &game);
+        notify_observer(&env, &game);
+        adjust_stats(&env, &game.codemaker, -1, 1);
+
+        GameForfeited {
+            session_id,
+            winner: codebreaker,
+            loser: game.codemaker,
+        }
+        .publish(&env);
+        log_event(&env, EventKind::Forfeited, session_id);
+
+        Ok(())
+    }
+
+    /// CodeBreaker claims a game the codemaker won but left unrevealed for
+    /// the whole dispute window, since their feedback can no longer be
+    /// checked. The game is awarded to the codebreaker as a timeout.
+    pub fn claim_reveal_timeout(
+        env: Env,
+        session_id: u32,
+        codebreaker: Address,
+    ) -> Result<(), Error> {
+        codebreaker.require_auth();
+
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        assert_phase(&game, GamePhase::Finished)?;
+        if game.codebreaker != codebreaker {
+            return Err(Error::NotCodeBreaker);
+        }
+        if !awaiting_reveal(&env, &game) {
+            return Err(Error::InvalidPhase);
+        }
+        if dispute_window_open(&env, &game) {
+            return Err(Error::TimeoutNotReached);
+        }
+
+        overturn_result(&env, &mut game, FinishReason::Timeout);
+
+        save_game(&env, &key, &game);
+        notify_observer(&env, &game);
+
+        GameTimedOut {
+            session_id,
+            loser: game.codemaker,
+            winner: codebreaker,
+        }
+        .publish(&env);
+        log_event(&env, EventKind::TimedOut, session_id);
+
+        Ok(())
+    }
+
+    /// Opens a provably-fair game: `seed` must match the committed seed hash,
+    /// and the secret derived from it must open the commitment with `salt`.
+    pub fn reveal_seed(
+        env: Env,
+        session_id: u32,
+        codemaker: Address,
+        seed: BytesN<32>,
+        salt: BytesN<32>,
+    ) -> Result<(), Error> {
+        let game = Self::get_game(env.clone(), session_id)?;
+        let (Some(seed_hash), Some(entropy)) =
+            (&game.fair_play.seed_hash, &game.fair_play.seed_entropy)
+        else {
+            return Err(Error::InvalidPhase);
+        };
+        let digest: BytesN<32> = env.crypto().sha256(&seed.clone().into()).into();
+        if digest != *seed_hash {
+            return Err(Error::CommitmentMismatch);
+        }
+
+        codemaker.require_auth();
+        let secret = seeded_secret(&env, &game, &seed, entropy);
+        open_commitment(&env, session_id, codemaker, secret, salt)
+    }
+
+    /// Get the current game state (read-only).
+    pub fn get_game(env: Env, session_id: u32) -> Result<GameState, Error> {
+        load_game(&env, &DataKey::Game(session_id)).ok_or(Error::GameNotFound)
+    }
+
+    /// Whether the game was moved to persistent storage with `persist_game`.
+    pub fn is_persistent(env: Env, session_id: u32) -> bool {
+        env.storage().persistent().has(&DataKey::Game(session_id))
+    }
+
+    /// The secret the codemaker opened with `reveal_code`.
+    pub fn get_revealed_secret(env: Env, session_id: u32) -> Result<Vec<u32>, Error> {
+        let game = Self::get_game(env, session_id)?;
+        if !game.revealed {
+            return Err(Error::InvalidPhase);
+        }
+        Ok(game.secret)
+    }
+
+    /// Whether `proof_hash` is already recorded against one of the game's
+    /// feedbacks.
+    pub fn has_seen_proof(
+        env: Env,
+        session_id: u32,
+        proof_hash: BytesN<32>,
+    ) -> Result<bool, Error> {
+        let game = Self::get_game(env, session_id)?;
+        Ok(has_seen_proof(&game, &proof_hash))
+    }
+
+    /// Whether feedback-circuit public inputs (see `Verifier`) belong to this
+    /// game: the right field count, the game's commitment first and, while a
+    /// guess awaits feedback, that guess's pegs. `submit_proven_feedback`
+    /// builds its inputs from the game, so this is for checking proofs
+    /// produced elsewhere.
+    pub fn validate_proof_binding(
+        env: Env,
+        session_id: u32,
+        public_inputs: Vec<BytesN<32>>,
+    ) -> Result<bool, Error> {
+        let game = Self::get_game(env.clone(), session_id)?;
+        if public_inputs.len() != game.code_length + 3
+            || public_inputs.first_unchecked() != game.commitment
+        {
+            return Ok(false);
+        }
+        if let Some(guess) = game.pending_guesses.first() {
+            for (i, peg) in guess.iter().enumerate() {
+                if public_inputs.get_unchecked(i as u32 + 1) != u32_field(&env, peg) {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Every phase the game has been in, oldest first, ending with the
+    /// current one.
+    pub fn get_phase_history(env: Env, session_id: u32) -> Result<Vec<GamePhase>, Error> {
+        let game = Self::get_game(env.clone(), session_id)?;
+        Ok(phase_history(&env, &game))
+    }
+
+    /// For a finished game, the answered guess that came closest: most red
+    /// pegs, then most white pegs, earliest first on a tie. Returned with its
+    /// red and white counts.
+    pub fn closest_guess(env: Env, session_id: u32) -> Result<(Vec<u32>, u32, u32), Error> {
+        let game = Self::get_game(env, session_id)?;
+        assert_phase(&game, GamePhase::Finished)?;
+
+        let mut closest: Option<(Vec<u32>, u32, u32)> = None;
+        for (guess, feedback) in game.guesses.iter().zip(game.feedbacks.iter()) {
+            let score = (feedback.correct_position, feedback.correct_color);
+            if closest
+                .as_ref()
+                .is_none_or(|(_, red, white)| score > (*red, *white))
+            {
+                closest = Some((guess, score.0, score.1));
+            }
+        }
+        closest.ok_or(Error::NoFeedbackYet)
+    }
+
+    /// Get the most recent feedback without the rest of the history.
+    pub fn get_last_feedback(env: Env, session_id: u32) -> Result<Feedback, Error> {
+        let game = Self::get_game(env, session_id)?;
+        game.feedbacks.last().ok_or(Error::NoFeedbackYet)
+    }
+
+    /// Guesses the codebreaker can still submit before running out hands the
+    /// codemaker the win. 0 once the game is decided.
+    pub fn guesses_until_codemaker_wins(env: Env, session_id: u32) -> Result<u32, Error> {
+        let game = Self::get_game(env, session_id)?;
+        Ok(remaining_guesses(&game))
+    }
+
+    /// Ledgers since the game entered its current phase.
+    pub fn time_in_current_phase(env: Env, session_id: u32) -> Result<u32, Error> {
+        let game = Self::get_game(env.clone(), session_id)?;
+        Ok(env.ledger().sequence() - game.phase_entered_ledger)
+    }
+
+    /// Ledgers left before the current phase's timeout can be claimed, 0 once
+    /// it can. In `WaitingForGuess` this is the `cancel_stale` deadline, which
+    /// only exists before the first answered guess; phases without a timeout
+    /// return `InvalidPhase`.
+    pub fn ledgers_until_timeout(env: Env, session_id: u32) -> Result<u32, Error> {
+        let game = Self::get_game(env.clone(), session_id)?;
+        let deadline = timeout_deadline(&env, &game).ok_or(Error::InvalidPhase)?;
+        Ok(deadline
+            .saturating_add(1)
+            .saturating_sub(env.ledger().sequence()))
+    }
+
+    /// The rules and answered history of a game, for automated solvers.
+    pub fn get_solver_state(env: Env, session_id: u32) -> Result<SolverState, Error> {
+        let game = Self::get_game(env, session_id)?;
+        Ok(SolverState {
+            code_length: game.code_length,
+            num_colors: game.num_colors,
+            allow_duplicates: true,
+            feedback_mode: game.feedback_mode,
+            remaining_guesses: remaining_guesses(&game),
+            guesses: game.guesses,
+            feedbacks: game.feedbacks,
+        })
+    }
+
+    /// The game's phase by its variant name, for logs and operators.
+    pub fn phase_name(env: Env, session_id: u32) -> Result<String, Error> {
+        let game = Self::get_game(env.clone(), session_id)?;
+        let name = match game.phase {
+            GamePhase::WaitingForCommitment => "WaitingForCommitment",
+            GamePhase::WaitingForGuess => "WaitingForGuess",
+            GamePhase::WaitingForFeedback => "WaitingForFeedback",
+            GamePhase::Finished => "Finished",
+            GamePhase::WaitingForAccept => "WaitingForAccept",
+        };
+        Ok(String::from_str(&env, name))
+    }
+
+    /// Tell `player` which action the game is waiting on from them.
+    pub fn next_action(env: Env, session_id: u32, player: Address) -> Result<ActionHint, Error> {
+        let game = Self::get_game(env, session_id)?;
+
+        let is_codemaker = player == game.codemaker;
+        let is_codebreaker = player == game.codebreaker;
+        if !is_codemaker && !is_codebreaker {
+            return Err(Error::NotPlayer);
+        }
+
+        let hint = match game.phase {
+            GamePhase::WaitingForAccept if is_codebreaker => ActionHint::Accept,
+            GamePhase::WaitingForCommitment if is_codemaker => ActionHint::Commit,
+            GamePhase::WaitingForGuess if is_codebreaker => ActionHint::Guess,
+            GamePhase::WaitingForFeedback if is_codemaker => ActionHint::GiveFeedback,
+            // A pipelining codebreaker may keep guessing while feedback is pending
+            GamePhase::WaitingForFeedback
+                if game.pending_guesses.len() < game.pipeline_depth
+                    && game.guess_count + game.pending_guesses.len() < guess_limit(&game) =>
+            {
+                ActionHint::Guess
+            }
+            GamePhase::Finished => ActionHint::GameOver,
+            _ => ActionHint::Wait,
+        };
+        Ok(hint)
+    }
+
+    /// Get how a finished game was decided.
+    pub fn get_finish_reason(env: Env, session_id: u32) -> Result<FinishReason, Error> {
+        let game = Self::get_game(env, session_id)?;
+        assert_phase(&game, GamePhase::Finished)?;
+        Ok(game.finish_reason)
+    }
+
+    /// sha256 of the XDR encoding of `get_config`, so clients and circuits can
+    /// bind to the exact rules in a single value. Any rule change, including
+    /// `adjust_max_guesses`, changes the digest.
+    pub fn rules_digest(env: Env, session_id: u32) -> Result<BytesN<32>, Error> {
+        let config = Self::get_config(env.clone(), session_id)?;
+        Ok(env.crypto().sha256(&config.to_xdr(&env)).into())
+    }
+
+    /// Every color a guess may use, `0..num_colors`, for rendering a picker.
+    pub fn legal_colors(env: Env, session_id: u32) -> Result<Vec<u32>, Error> {
+        let game = Self::get_game(env.clone(), session_id)?;
+        let mut colors = Vec::new(&env);
+        for color in 0..game.num_colors {
+            colors.push_back(color);
+        }
+        Ok(colors)
+    }
+
+    /// Display name of one color. Fails with `InvalidGuessValue` when the
+    /// color is out of range or the game has no labels.
+    pub fn get_color_label(env: Env, session_id: u32, color: u32) -> Result<String, Error> {
+        let game = Self::get_game(env, session_id)?;
+        game.color_labels.get(color).ok_or(Error::InvalidGuessValue)
+    }
+
+    /// Get the rules a game was created with.
+    pub fn get_config(env: Env, session_id: u32) -> Result<GameConfig, Error> {
+        let game = Self::get_game(env, session_id)?;
+        Ok(GameConfig {
+            code_length: game.code_length,
+            num_colors: game.num_colors,
+            max_guesses: game.max_guesses,
+            pipeline_depth: game.pipeline_depth,
+            stake_token: game.stake_token,
+            codemaker_stake: game.codemaker_stake,
+            codebreaker_stake: game.codebreaker_stake,
+            draw_on_exhaustion: game.draw_on_exhaustion,
+            commit_scheme: game.commit_scheme,
+            trusted: game.trusted,
+            hints_allowed: game.hints_allowed,
+            strict_proofs: game.strict_proofs,
+            color_labels: game.color_labels,
+            feedback_mode: game.feedback_mode,
+            validity_proof: game.validity_proof,
+            sealed_feedback_allowed: game.sealed_feedback_allowed,
+            commit_deadline_ledgers: game.fair_play.commit_deadline_ledgers,
+        })
+    }
+
+    /// Export the full game record (rules, commitment, history, outcome and
+    /// timings) as a single value.
+    pub fn export_transcript(env: Env, session_id: u32) -> Result<Transcript, Error> {
+        let game = Self::get_game(env, session_id)?;
+        // Split feedback shows a solve on its own; a combined score relies on
+        // the codemaker's word, which only survives as the finish reason
+        let solved_at = if game.feedback_mode == FEEDBACK_COMBINED {
+            (game.finish_reason == FinishReason::Solved).then(|| game.feedbacks.len() - 1)
+        } else {
+            game.feedbacks
+                .iter()
+                .position(|feedback| feedback.correct_position == game.code_length)
+                .map(|index| index as u32)
+        };
+        Ok(Transcript {
+            session_id: game.session_id,
+            codemaker: game.codemaker,
+            codebreaker: game.codebreaker,
+            code_length: game.code_length,
+            num_colors: game.num_colors,
+            max_guesses: game.max_guesses,
+            feedback_mode: game.feedback_mode,
+            commitment: game.commitment,
+            guesses: game.guesses,
+            feedbacks: game.feedbacks,
+            solved_at,
+            winner: game.winner,
+            created_ledger: game.created_ledger,
+            finished_ledger: game.finished_ledger,
+            finish_reason: game.finish_reason,
+            hints: game.hints,
+        })
+    }
+
+    /// Check a transcript is internally consistent: every guess and feedback
+    /// is well formed, play stopped at the solving feedback, and the declared
+    /// winner is the one the rules give for its finish reason, taking the
+    /// feedbacks as ground truth. Under combined scoring a full score only
+    /// solves where `solved_at` says so, as a permutation scores full too.
+    pub fn verify_transcript(_env: Env, transcript: Transcript) -> bool {
+        let t = &transcript;
+        let played = t.feedbacks.len();
+        if t.guesses.len() != played || played + t.hints.len() > t.max_guesses {
+            return false;
+        }
+        let combined = match t.feedback_mode {
+            FEEDBACK_SPLIT => false,
+            FEEDBACK_COMBINED => true,
+            _ => return false,
+        };
+        // The solve is the last feedback and shows every peg in place
+        if let Some(index) = t.solved_at {
+            if index + 1 != played
+                || t.feedbacks.get(index).unwrap().correct_position != t.code_length
+            {
+                return false;
+            }
+        }
+        for (i, (guess, feedback)) in t.guesses.iter().zip(t.feedbacks.iter()).enumerate() {
+            if guess.len() != t.code_length || guess.iter().any(|peg| peg >= t.num_colors) {
+                return false;
+            }
+            // Counts come from the caller, so they may be anywhere in range
+            let pegs = feedback
+                .correct_position
+                .checked_add(feedback.correct_color);
+            if pegs.is_none_or(|pegs| pegs > t.code_length) {
+                return false;
+            }
+            // A combined score has no white pegs
+            if combined && feedback.correct_color != 0 {
+                return false;
+            }
+            // Split feedback with every peg in place is always the solve
+            if !combined
+                && feedback.correct_position == t.code_length
+                && t.solved_at != Some(i as u32)
+            {
+                return false;
+            }
+        }
+
+        let solved = t.solved_at.is_some();
+        let exhausted = !solved && played + t.hints.len() == t.max_guesses;
+        let codemaker = Some(t.codemaker.clone());
+        let codebreaker = Some(t.codebreaker.clone());
+        match t.finish_reason {
+            FinishReason::Unfinished => t.winner.is_none() && !solved && !exhausted,
+            FinishReason::Solved => solved && t.winner == codebreaker,
+            FinishReason::Exhausted => exhausted && t.winner == codemaker,
+            FinishReason::Draw => exhausted && t.winner.is_none(),
+            FinishReason::Resigned => !solved && !exhausted && t.winner == codemaker,
+            FinishReason::Forfeit => !solved && !exhausted && t.winner == codebreaker,
+            FinishReason::FalseFeedback => t.winner == codebreaker,
+            FinishReason::Timeout | FinishReason::AdminSettled => {
+                t.winner == codemaker || t.winner == codebreaker
+            }
+        }
+    }
+
+    /// Lower bound on how many pegs of each color the secret must contain,
+    /// derived only from recorded feedback. For a guess with `g` pegs of color
+    /// `c` and `r + w` total matches, at most `code_length - g` matches can come
+    /// from other colors, so the secret holds at least `r + w - (code_length - g)`
+    /// pegs of `c`. Index `i` of the result is color `i`.
+    pub fn inferred_colors(env: Env, session_id: u32) -> Result<Vec<u32>, Error> {
+        let game = Self::get_game(env.clone(), session_id)?;
+
+        let mut bounds = Vec::new(&env);
+        for _ in 0..game.num_colors {
+            bounds.push_back(0u32);
+        }
+
+        for (guess, feedback) in game.guesses.iter().zip(game.feedbacks.iter()) {
+            let matches = feedback.correct_position + feedback.correct_color;
+            let counts = color_counts(&env, &guess, game.num_colors);
+            for color in 0..game.num_colors {
+                let bound = (matches + counts.get(color).unwrap()).saturating_sub(game.code_length);
+                if bound > bounds.get(color).unwrap() {
+                    bounds.set(color, bound);
+                }
+            }
+        }
+
+        Ok(bounds)
+    }
+
+    /// Suggest the codebreaker's next guess: of the codes consistent with all
+    /// recorded feedback, the one that minimizes the worst-case number of
+    /// codes still consistent afterwards. Games with more than
+    /// `SUGGEST_SEARCH_LIMIT` possible codes are refused with `InvalidConfig`.
+    /// While more than `SUGGEST_MINIMAX_LIMIT` codes remain consistent, the
+    /// first consistent code is returned unscored to stay within budget.
+    pub fn suggest_next_guess(env: Env, session_id: u32) -> Result<Vec<u32>, Error> {
+        let game = Self::get_game(env.clone(), session_id)?;
+        if game.phase == GamePhase::Finished {
+            return Err(Error::GameAlreadyEnded);
+        }
+        let space = game
+            .num_colors
+            .checked_pow(game.code_length)
+            .filter(|space| *space <= SUGGEST_SEARCH_LIMIT)
+            .ok_or(Error::InvalidConfig)?;
+
+        let (history, answered) = feedback_history(&game);
+        let history = &history[..answered];
+
+        let len = game.code_length as usize;
+        let mut candidates: [Code; SUGGEST_MINIMAX_LIMIT] =
+            [[0; MAX_CODE_LENGTH as usize]; SUGGEST_MINIMAX_LIMIT];
+        let mut consistent = 0;
+        for index in 0..space {
+            let code = nth_code(index, game.code_length, game.num_colors);
+            if !is_consistent(&code[..len], history, game.num_colors, game.feedback_mode) {
+                continue;
+            }
+            if consistent < SUGGEST_MINIMAX_LIMIT {
+                candidates[consistent] = code;
+            }
+            consistent += 1;
+        }
+
+        let best = match consistent {
+            // No code fits the feedback, so some of it was wrong
+            0 => return Err(Error::InvalidFeedback),
+            n if n > SUGGEST_MINIMAX_LIMIT => candidates[0],
+            n => minimax_guess(&candidates[..n], len, game.num_colors, game.feedback_mode),
+        };
+        Ok(Vec::from_slice(&env, &best[..len]))
+    }
+
+    /// Per color: -1 if it is proven absent from the secret, otherwise the
+    /// minimum number of times it is proven present (0 when unknown). Exact
+    /// for games of up to `SUGGEST_SEARCH_LIMIT` codes; larger games fall back
+    /// to the `inferred_colors` bounds plus colors seen in a zero-match guess.
+    pub fn color_knowledge(env: Env, session_id: u32) -> Result<Vec<i32>, Error> {
+        let game = Self::get_game(env.clone(), session_id)?;
+        let colors = game.num_colors as usize;
+        let mut fewest = [u32::MAX; MAX_NUM_COLORS as usize];
+        let mut most = [0u32; MAX_NUM_COLORS as usize];
+
+        let searchable = matches!(
+            game.num_colors.checked_pow(game.code_length),
+            Some(space) if space <= SUGGEST_SEARCH_LIMIT
+        );
+        let mut consistent = 0;
+        if searchable {
+            let (history, answered) = feedback_history(&game);
+            let len = game.code_length as usize;
+            for index in 0..game.num_colors.pow(game.code_length) {
+                let code = nth_code(index, game.code_length, game.num_colors);
+                if !is_consistent(
+                    &code[..len],
+                    &history[..answered],
+                    game.num_colors,
+                    game.feedback_mode,
+                ) {
+                    continue;
+                }
+                consistent += 1;
+                let mut counts = [0u32; MAX_NUM_COLORS as usize];
+                for color in &code[..len] {
+                    counts[*color as usize] += 1;
+                }
+                for color in 0..colors {
+                    fewest[color] = fewest[color].min(counts[color]);
+                    most[color] = most[color].max(counts[color]);
+                }
+            }
+        }
+
+        // Too many codes to search, or the feedback contradicts itself
+        if consistent == 0 {
+            let bounds = Self::inferred_colors(env.clone(), session_id)?;
+            for color in 0..colors {
+                fewest[color] = bounds.get(color as u32).unwrap();
+                most[color] = game.code_length;
+            }
+            for (guess, feedback) in game.guesses.iter().zip(game.feedbacks.iter()) {
+                if feedback.correct_position + feedback.correct_color == 0 {
+                    for color in guess.iter() {
+                        most[color as usize] = 0;
+                    }
+                }
+            }
+        }
+
+        let mut knowledge = Vec::new(&env);
+        for color in 0..colors {
+            knowledge.push_back(if most[color] == 0 {
+                -1
+            } else {
+                fewest[color] as i32
+            });
+        }
+        Ok(knowledge)
+    }
+
+    /// How many codes are still consistent with all recorded feedback. Games
+    /// with more than `SUGGEST_SEARCH_LIMIT` possible codes are not searched
+    /// and report `u32::MAX`.
+    pub fn remaining_candidates(env: Env, session_id: u32) -> Result<u32, Error> {
+        let game = Self::get_game(env, session_id)?;
+        let space = match game.num_colors.checked_pow(game.code_length) {
+            Some(space) if space <= SUGGEST_SEARCH_LIMIT => space,
+            _ => return Ok(u32::MAX),
+        };
+
+        let (history, answered) = feedback_history(&game);
+        let len = game.code_length as usize;
+        let remaining = (0..space)
+            .filter(|index| {
+                let code = nth_code(*index, game.code_length, game.num_colors);
+                is_consistent(
+                    &code[..len],
+                    &history[..answered],
+                    game.num_colors,
+                    game.feedback_mode,
+                )
+            })
+            .count();
+        Ok(remaining as u32)
+    }
+
+    /// Find an unfinished game between `a` and `b` (in either role), so a lobby
+    /// can avoid pairing the same players twice. Scans `a`'s recent sessions.
+    pub fn active_game_between(env: Env, a: Address, b: Address) -> Option<u32> {
+        for session_id in player_games(&env, &a).iter() {
+            let game = load_game(&env, &DataKey::Game(session_id));
+            if let Some(game) = game {
+                let paired = (game.codemaker == a && game.codebreaker == b)
+                    || (game.codemaker == b && game.codebreaker == a);
+                if paired && game.phase != GamePhase::Finished {
+                    return Some(session_id);
+                }
+            }
+        }
+        None
+    }
+
+    /// Sessions in `codemaker`'s recent games that are waiting on their
+    /// feedback, so a bot can find all its pending work in one call.
+    pub fn needs_my_feedback(env: Env, codemaker: Address) -> Vec<u32> {
+        let mut waiting = Vec::new(&env);
+        for session_id in player_games(&env, &codemaker).iter() {
+            let game = load_game(&env, &DataKey::Game(session_id));
+            if let Some(game) = game {
+                if game.codemaker == codemaker && game.phase == GamePhase::WaitingForFeedback {
+                    waiting.push_back(session_id);
+                }
+            }
+        }
+        waiting
+    }
+
+    /// Unfinished games currently in `phase`, oldest first, skipping the
+    /// first `offset` matches and returning at most `limit` (capped at
+    /// `PHASE_QUERY_LIMIT`). Only the `ACTIVE_INDEX_LIMIT` most recent games
+    /// are tracked.
+    pub fn games_in_phase(env: Env, phase: GamePhase, offset: u32, limit: u32) -> Vec<u32> {
+        let limit = limit.min(PHASE_QUERY_LIMIT);
+        let mut found = Vec::new(&env);
+        let mut skipped = 0;
+        for session_id in active_games_index(&env).iter() {
+            if found.len() >= limit {
+                break;
+            }
+            let game = load_game(&env, &DataKey::Game(session_id));
+            if !game.is_some_and(|game| game.phase == phase) {
+                continue;
+            }
+            if skipped < offset {
+                skipped += 1;
+            } else {
+                found.push_back(session_id);
+            }
+        }
+        found
+    }
+
+    /// `player`'s finished games among their recent sessions, newest first,
+    /// as `(session_id, finish_reason, won)`. Skips the first `offset` and
+    /// returns at most `limit`; the index holds `PLAYER_INDEX_LIMIT` sessions.
+    pub fn get_player_history(
+        env: Env,
+        player: Address,
+        offset: u32,
+        limit: u32,
+    ) -> Vec<(u32, FinishReason, bool)> {
+        let mut history = Vec::new(&env);
+        let mut skipped = 0;
+        for session_id in player_games(&env, &player).iter().rev() {
+            if history.len() >= limit {
+                break;
+            }
+            let game = load_game(&env, &DataKey::Game(session_id));
+            let Some(game) = game.filter(|game| game.phase == GamePhase::Finished) else {
+                continue;
+            };
+            if skipped < offset {
+                skipped += 1;
+                continue;
+            }
+            let won = game.winner == Some(player.clone());
+            history.push_back((session_id, game.finish_reason, won));
+        }
+        history
+    }
+
+    /// `player`'s feedback track record as a codemaker, so others can avoid
+    /// known cheaters.
+    pub fn get_stats(env: Env, player: Address) -> PlayerStats {
+        player_stats(&env, &player)
+    }
+
+    /// How many games are in each phase, for spotting games stuck waiting on
+    /// one side. Updated on every phase change.
+    pub fn get_phase_counts(env: Env) -> PhaseCounts {
+        phase_counts(&env)
+    }
+
+    /// Up to `limit` of the most recent game events, newest first, for
+    /// indexers that poll instead of streaming. Only the last
+    /// `EVENT_LOG_LIMIT` events are kept.
+    pub fn recent_events(env: Env, limit: u32) -> Vec<EventRecord> {
+        let log = event_log(&env);
+        let mut recent = Vec::new(&env);
+        for record in log.iter().rev().take(limit as usize) {
+            recent.push_back(record);
+        }
+        recent
+    }
+
+    /// Canonical commitment to a finished game's outcome for bridging:
+    /// sha256 of the XDR encoding of
+    /// `(session_id, winner, guess_count, commitment, final_feedback)`.
+    pub fn result_hash(env: Env, session_id: u32) -> Result<BytesN<32>, Error> {
+        let game = Self::get_game(env.clone(), session_id)?;
+        assert_phase(&game, GamePhase::Finished)?;
+        Ok(result_hash(&env, &game))
+    }
+
+    /// Whether `claimed` is the `result_hash` of this finished game, so a
+    /// light client can confirm an off-chain claim about the outcome.
+    pub fn check_result(env: Env, session_id: u32, claimed: BytesN<32>) -> Result<bool, Error> {
+        Ok(Self::result_hash(env, session_id)? == claimed)
+    }
+
+    /// Root over the `result_hash` of every game in the order they finished,
+    /// so a game's inclusion can be proven off-chain. Leaves are merged
+    /// pairwise as `sha256(left || right)` into a merkle mountain range
+    /// whose peaks are bagged right to left. A game whose result is later
+    /// overturned gets another leaf for the corrected outcome, so a game's
+    /// latest leaf is its final result. All zeroes before any game ends.
+    pub fn get_history_root(env: Env) -> BytesN<32> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::HistoryRoot)
+            .unwrap_or(BytesN::from_array(&env, &[0u8; 32]))
+    }
+
+    /// Get the verifier contract address (for client-side reference).
+    pub fn get_verifier(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::VerifierAddress)
+            .expect("Verifier not set")
+    }
+
+    /// Get the admin, hub, verifier and treasury in one call.
+    pub fn get_addresses(env: Env) -> Result<Addresses, Error> {
+        let storage = env.storage().instance();
+        Ok(Addresses {
+            admin: storage.get(&DataKey::Admin).ok_or(Error::NotInitialized)?,
+            game_hub: storage
+                .get(&DataKey::GameHubAddress)
+                .ok_or(Error::NotInitialized)?,
+            verifier: storage
+                .get(&DataKey::VerifierAddress)
+                .ok_or(Error::NotInitialized)?,
+            treasury: storage.get(&DataKey::Treasury),
+        })
+    }
+
+    /// Get the stored verification key for the default feedback circuit.
+    pub fn get_vk(env: Env) -> Option<Bytes> {
+        env.storage().persistent().get(&DataKey::VerificationKey)
+    }
+
+    /// Get the verifier used for games with these rules: the one registered
+    /// for them, otherwise the global verifier.
+    pub fn get_verifier_for(env: Env, code_length: u32, num_colors: u32) -> Address {
+        verifier_address(&env, code_length, num_colors)
+    }
+
+    /// Get the timeout windows currently applied to every game.
+    pub fn get_timeout_config(env: Env) -> TimeoutConfig {
+        timeout_config(&env)
+    }
+
+    /// Report game result to Game Hub. Can be called by anyone after game
+    /// ends, once per game.
+    pub fn report_result(env: Env, session_id: u32) -> Result<(), Error> {
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        assert_phase(&game, GamePhase::Finished)?;
+        if game.reported {
+            return Err(Error::AlreadyReported);
+        }
+        if is_practice(&env, &game) {
+            return Err(Error::PracticeGame);
+        }
+        // The hub only records a winner, so draws are not reported
+        if game.winner.is_none() {
+            return Err(Error::NoWinner);
+        }
+        if dispute_window_open(&env, &game) {
+            return Err(Error::DisputeWindowOpen);
+        }
+        if awaiting_reveal(&env, &game) {
+            return Err(Error::RevealPending);
+        }
+
+        game.reported = true;
+        save_game(&env, &key, &game);
+        try_report_to_hub(&env, &game)
+    }
+
+    /// Pay out a finished staked game once its dispute window has closed. The
+    /// winner collects both stakes, less any resignation penalty; on a draw
+    /// either player may trigger the refund of each player's own stake.
+    /// Returns the amount sent to `player`.
+    pub fn withdraw_winnings(env: Env, session_id: u32, player: Address) -> Result<i128, Error> {
+        player.require_auth();
+
+        let key = DataKey::Game(session_id);
+        let mut game: GameState = load_game(&env, &key).ok_or(Error::GameNotFound)?;
+
+        assert_phase(&game, GamePhase::Finished)?;
+        let token = game.stake_token.clone().ok_or(Error::NoStake)?;
+        if game.settled {
+            return Err(Error::AlreadySettled);
+        }
+        match &game.winner {
+            Some(winner) if *winner != player => return Err(Error::NotWinner),
+            None if player != game.codemaker && player != game.codebreaker => {
+                return Err(Error::NotPlayer)
+            }
+            _ => {}
+        }
+        // The result may still be overturned by `dispute_feedback`
+        if dispute_window_open(&env, &game) {
+            return Err(Error::DisputeWindowOpen);
+        }
+        if awaiting_reveal(&env, &game) {
+            return Err(Error::RevealPending);
+        }
+
+        game.settled = true;
+        save_game(&env, &key, &game);
+
+        pay_out(&env, &game, &token);
+        Ok(payout_for(&env, &game, &player))
+    }
+
+    /// What `player` would receive from `withdraw_winnings` right now: the
+    /// pot less any resign penalty if they won, their own stake back on a
+    /// draw, and nothing if they lost or the game is already settled. Fails
+    /// with `DisputeWindowOpen` while the result can still be disputed, and
+    /// with `RevealPending` while a codemaker's win waits on its reveal.
+    pub fn pending_payout(env: Env, session_id: u32, player: Address) -> Result<i128, Error> {
+        let game = Self::get_game(env.clone(), session_id)?;
+        assert_phase(&game, GamePhase::Finished)?;
+        if game.stake_token.is_none() {
+            return Err(Error::NoStake);
+        }
+        if dispute_window_open(&env, &game) {
+            return Err(Error::DisputeWindowOpen);
+        }
+        if awaiting_reveal(&env, &game) {
+            return Err(Error::RevealPending);
+        }
+        if game.settled {
+            return Ok(0);
+        }
+        Ok(payout_for(&env, &game, &player))
+    }
+
+    /// Keeper entrypoint: report and pay out up to `SETTLE_BATCH_LIMIT`
+    /// finished games in one call. Each game is marked before any external
+    /// call is made. Games that are missing, unfinished or have nothing left
+    /// to report or pay are skipped, as are games the hub fails to record,
+    /// which stay unreported and unpaid for a later call. Returns the number
+    /// of games settled.
+    pub fn settle_many(env: Env, session_ids: Vec<u32>) -> Result<u32, Error> {
+        if session_ids.len() > SETTLE_BATCH_LIMIT {
+            return Err(Error::BatchTooLarge);
+        }
+
+        let mut settled = 0;
+        for session_id in session_ids.iter() {
+            let key = DataKey::Game(session_id);
+            let Some(mut game) = load_game(&env, &key) else {
+                continue;
+            };
+            if game.phase != GamePhase::Finished {
+                continue;
+            }
+            // Draws have nothing to report; unstaked games nothing to pay.
+            // Neither happens while the result can still be disputed.
+            let disputable = dispute_window_open(&env, &game) || awaiting_reveal(&env, &game);
+            let report =
+                !game.reported && game.winner.is_some() && !is_practice(&env, &game) && !disputable;
+            let pay = !game.settled && game.stake_token.is_some() && !disputable;
+            if !report && !pay {
+                continue;
+            }
+
+            if report {
+                game.reported = true;
+                save_game(&env, &key, &game);
+                if try_report_to_hub(&env, &game).is_err() {
+                    game.reported = false;
+                    save_game(&env, &key, &game);
+                    continue;
+                }
+            }
+            if let Some(token) = game.stake_token.clone().filter(|_| pay) {
+                game.settled = true;
+                save_game(&env, &key, &game);
+                pay_out(&env, &game, &token);
+            }
+            settled += 1;
+        }
+
+        Ok(settled)
+    }
+
+    /// Sum of all stakes in `token` the contract currently holds for games,
+    /// so anyone can check it against the contract's token balance.
+    pub fn total_escrow(env: Env, token: Address) -> i128 {
+        total_escrow(&env, &token)
+    }
+
+    // ========================================================================
+    // Tournaments
+    // ========================================================================
+
+    /// Admin seeds up to `SEED_BATCH_LIMIT` games at once, each with its own
+    /// pair and rules, under fresh auto-assigned session ids returned in spec
+    /// order. Every game starts waiting for its codebreaker to accept. The
+    /// admin cannot escrow for players, so specs must be unstaked. Each player
+    /// is held to the per-player game cap, counting games earlier in the
+    /// batch. Nothing is created if any spec is refused.
+    pub fn new_games(env: Env, specs: Vec<GameSpec>) -> Result<Vec<u32>, Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_accepting_games(&env)?;
+
+        if specs.len() > SEED_BATCH_LIMIT {
+            return Err(Error::BatchTooLarge);
+        }
+        for spec in specs.iter() {
+            validate_config(&spec.config)?;
+            if spec.config.stake_token.is_some() {
+                return Err(Error::InvalidConfig);
+            }
+        }
+
+        let mut sessions = Vec::new(&env);
+        for spec in specs.iter() {
+            check_game_limit(&env, &spec.codemaker)?;
+            check_game_limit(&env, &spec.codebreaker)?;
+            let session_id = next_match_id(&env)?;
+            let game = new_game_state(
+                &env,
+                session_id,
+                spec.codemaker,
+                spec.codebreaker,
+                &spec.config,
+            );
+            save_game(&env, &DataKey::Game(session_id), &game);
+            index_game(&env, &game);
+            notify_observer(&env, &game);
+            sessions.push_back(session_id);
+        }
+        Ok(sessions)
+    }
+
+    /// Seed a single-elimination bracket. Players are paired in order, the
+    /// first of each pair as codemaker, and every match starts waiting for
+    /// the codebreaker to accept. The player count must be a power of two,
+    /// and every player must have room under the per-player game cap.
+    pub fn new_tournament(env: Env, players: Vec<Address>) -> Result<u32, Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_accepting_games(&env)?;
+
+        if players.len() < 2 || !players.len().is_power_of_two() {
+            return Err(Error::InvalidConfig);
+        }
+
+        let tournament_id: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::TournamentCount)
+            .unwrap_or(0)
+            + 1;
+        env.storage()
+            .instance()
+            .set(&DataKey::TournamentCount, &tournament_id);
+
+        let mut tournament = Tournament {
+            players: players.clone(),
+            round: 0,
+            matches: Vec::new(&env),
+            entrants: Vec::new(&env),
+            walkovers: Map::new(&env),
+            champion: None,
+        };
+        start_round(&env, tournament_id, &mut tournament, &players)?;
+
+        Ok(tournament_id)
+    }
+
+    /// Once every match in the current round is decided or awarded by
+    /// `award_walkover`, pair the winners into the next round, or crown the
+    /// champion after the final. Fails with `TooManyActiveGames` while a
+    /// winner is at the per-player game cap; call again once they have room.
+    pub fn advance_tournament(env: Env, tournament_id: u32) -> Result<(), Error> {
+        let mut tournament = Self::get_tournament(env.clone(), tournament_id)?;
+        if tournament.champion.is_some() {
+            return Err(Error::GameAlreadyEnded);
+        }
+
+        let mut winners = Vec::new(&env);
+        for session_id in tournament.matches.iter() {
+            if let Some(winner) = tournament.walkovers.get(session_id) {
+                winners.push_back(winner);
+                continue;
+            }
+            let game = Self::get_game(env.clone(), session_id)?;
+            assert_phase(&game, GamePhase::Finished)?;
+            winners.push_back(game.winner.ok_or(Error::NoWinner)?);
+        }
+
+        if winners.len() == 1 {
+            let champion = winners.get_unchecked(0);
+            tournament.champion = Some(champion.clone());
+            save_tournament(&env, tournament_id, &tournament);
+            TournamentWon {
+                tournament_id,
+                champion,
+            }
+            .publish(&env);
+            return Ok(());
+        }
+
+        start_round(&env, tournament_id, &mut tournament, &winners)
+    }
+
+    /// Admin decides a current-round match that can no longer produce a
+    /// winner: its game was cancelled or expired, or ended in a draw. The
+    /// winner must be one of the match's two players.
+    pub fn award_walkover(
+        env: Env,
+        tournament_id: u32,
+        session_id: u32,
+        winner: Address,
+    ) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+
+        let mut tournament = Self::get_tournament(env.clone(), tournament_id)?;
+        if tournament.champion.is_some() {
+            return Err(Error::GameAlreadyEnded);
+        }
+        let index = tournament
+            .matches
+            .first_index_of(session_id)
+            .ok_or(Error::GameNotFound)?;
+        if let Some(game) = load_game(&env, &DataKey::Game(session_id)) {
+            if game.phase != GamePhase::Finished || game.winner.is_some() {
+                return Err(Error::InvalidPhase);
+            }
+        }
+        let first = tournament.entrants.get_unchecked(2 * index);
+        let second = tournament.entrants.get_unchecked(2 * index + 1);
+        if winner != first && winner != second {
+            return Err(Error::NotPlayer);
+        }
+
+        tournament.walkovers.set(session_id, winner);
+        save_tournament(&env, tournament_id, &tournament);
+        Ok(())
+    }
+
+    pub fn get_tournament(env: Env, tournament_id: u32) -> Result<Tournament, Error> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Tournament(tournament_id))
+            .ok_or(Error::GameNotFound)
+    }
+
+    // ========================================================================
+    // Dual Games
+    // ========================================================================
+
+    /// Start a dual game: two sessions, one with each player as codemaker.
+    /// Both players authorize, so both sessions skip the accept handshake
+    /// and wait for the commitments. Each session plays and reports like any
+    /// other game; `resolve_dual_game` compares them.
+    pub fn new_dual_game(env: Env, player_a: Address, player_b: Address) -> Result<u32, Error> {
+        player_a.require_auth();
+        player_b.require_auth();
+        check_accepting_games(&env)?;
+        if player_a == player_b {
+            return Err(Error::InvalidConfig);
+        }
+        // Each player joins both sessions
+        for player in [&player_a, &player_b] {
+            if active_games(&env, player) + 2 > max_games_per_player(&env) {
+                return Err(Error::TooManyActiveGames);
+            }
+        }
+
+        let config = default_config(&env);
+        let mut sessions = Vec::new(&env);
+        for (codemaker, codebreaker) in [
+            (player_a.clone(), player_b.clone()),
+            (player_b.clone(), player_a.clone()),
+        ] {
+            let session_id = next_match_id(&env)?;
+            let mut game = new_game_state(&env, session_id, codemaker, codebreaker, &config);
+            set_phase(&env, &mut game, GamePhase::WaitingForCommitment);
+            save_game(&env, &DataKey::Game(session_id), &game);
+            index_game(&env, &game);
+            register_with_hub(&env, &game);
+            notify_observer(&env, &game);
+            sessions.push_back(session_id);
+        }
+
+        let dual_id: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::DualGameCount)
+            .unwrap_or(0)
+            + 1;
+        env.storage()
+            .instance()
+            .set(&DataKey::DualGameCount, &dual_id);
+        save_dual_game(
+            &env,
+            dual_id,
+            &DualGame {
+                player_a,
+                player_b,
+                game_a: sessions.get_unchecked(0),
+                game_b: sessions.get_unchecked(1),
+                decided: false,
+                winner: None,
+            },
+        );
+
+        Ok(dual_id)
+    }
+
+    /// Decide a dual game once the result can no longer change: both
+    /// sessions are over, or one player has solved in `n` guesses while the
+    /// other has used `n` without solving. Equal counts, or neither solving,
+    /// is a draw. A player who loses the session they set the code for to
+    /// the codebreaker other than by a solve (timeout, forfeit, false
+    /// feedback) loses the dual outright. A session that was cancelled or has
+    /// expired voids the dual as a draw.
+    pub fn resolve_dual_game(env: Env, dual_id: u32) -> Result<(), Error> {
+        let mut dual = Self::get_dual_game(env.clone(), dual_id)?;
+        if dual.decided {
+            return Err(Error::GameAlreadyEnded);
+        }
+
+        // player_a breaks game_b's code and player_b breaks game_a's
+        let a = dual_progress(load_game(&env, &DataKey::Game(dual.game_b)));
+        let b = dual_progress(load_game(&env, &DataKey::Game(dual.game_a)));
+        let winner = match (a, b) {
+            (DualProgress::Forfeited, DualProgress::Forfeited) => None,
+            (DualProgress::Forfeited, _) => Some(dual.player_a.clone()),
+            (_, DualProgress::Forfeited) => Some(dual.player_b.clone()),
+            (DualProgress::Void, _) | (_, DualProgress::Void) => None,
+            (DualProgress::Solved(a), DualProgress::Solved(b)) if a == b => None,
+            (DualProgress::Solved(a), DualProgress::Solved(b)) if a < b => {
+                Some(dual.player_a.clone())
+            }
+            (DualProgress::Solved(_), DualProgress::Solved(_)) => Some(dual.player_b.clone()),
+            (DualProgress::Solved(_), DualProgress::Failed) => Some(dual.player_a.clone()),
+            (DualProgress::Failed, DualProgress::Solved(_)) => Some(dual.player_b.clone()),
+            (DualProgress::Solved(a), DualProgress::Playing(used)) if used >= a => {
+                Some(dual.player_a.clone())
+            }
+            (DualProgress::Playing(used), DualProgress::Solved(b)) if used >= b => {
+                Some(dual.player_b.clone())
+            }
+            (DualProgress::Failed, DualProgress::Failed) => None,
+            _ => return Err(Error::InvalidPhase),
+        };
+
+        dual.decided = true;
+        dual.winner = winner.clone();
+        save_dual_game(&env, dual_id, &dual);
+        DualGameDecided { dual_id, winner }.publish(&env);
+
+        Ok(())
+    }
+
+    pub fn get_dual_game(env: Env, dual_id: u32) -> Result<DualGame, Error> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::DualGame(dual_id))
+            .ok_or(Error::GameNotFound)
+    }
+
+    // ========================================================================
+    // Admin Functions
+    // ========================================================================
+
+    pub fn get_admin(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set")
+    }
+
+    /// Whether `who` is the admin, so a frontend can show admin controls
+    /// without trying a call that would fail on auth.
+    pub fn is_admin(env: Env, who: Address) -> bool {
+        env.storage().instance().get(&DataKey::Admin) == Some(who)
+    }
+
+    pub fn set_admin(env: Env, new_admin: Address) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+        env.storage().instance().set(&DataKey::Admin, &new_admin);
+        Ok(())
+    }
+
+    pub fn set_verifier(env: Env, new_verifier: Address) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+        env.storage()
+            .instance()
+            .set(&DataKey::VerifierAddress, &new_verifier);
+        Ok(())
+    }
+
+    /// Register the verifier for the circuit compiled for
+    /// `(code_length, num_colors)`.
+    pub fn set_verifier_for(
+        env: Env,
+        code_length: u32,
+        num_colors: u32,
+        verifier: Address,
+    ) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+        env.storage()
+            .instance()
+            .set(&DataKey::VerifierFor(code_length, num_colors), &verifier);
+        Ok(())
+    }
+
+    /// Store the verification key of the default feedback circuit. Proofs
+    /// checked by the global verifier are then verified against it instead
+    /// of the verifier loading its own; games with a per-rules verifier from
+    /// `set_verifier_for` are unaffected.
+    pub fn set_vk(env: Env, vk: Bytes) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+        if vk.is_empty() {
+            return Err(Error::InvalidConfig);
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::VerificationKey, &vk);
+        env.storage().persistent().extend_ttl(
+            &DataKey::VerificationKey,
+            GAME_TTL_LEDGERS,
+            GAME_TTL_LEDGERS,
+        );
+        Ok(())
+    }
+
+    pub fn set_hub(env: Env, new_hub: Address) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+        env.storage()
+            .instance()
+            .set(&DataKey::GameHubAddress, &new_hub);
+        Ok(())
+    }
+
+    /// Set the ratings contract whose points are sent to the hub when a game
+    /// is registered. Without one, both players start at 0.
+    pub fn set_ratings(env: Env, ratings: Address) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+
+        env.storage()
+            .instance()
+            .set(&DataKey::RatingsAddress, &ratings);
+        Ok(())
+    }
+
+    pub fn get_ratings(env: Env) -> Option<Address> {
+        env.storage().instance().get(&DataKey::RatingsAddress)
+    }
+
+    /// Cap how many unfinished games a player may be in when a new one is
+    /// created. Finished games free their slot.
+    pub fn set_max_games_per_player(env: Env, limit: u32) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+
+        if limit == 0 {
+            return Err(Error::InvalidConfig);
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::MaxGamesPerPlayer, &limit);
+        Ok(())
+    }
+
+    pub fn get_max_games_per_player(env: Env) -> u32 {
+        max_games_per_player(&env)
+    }
+
+    /// Add a token to the stake allowlist. Once the list is non-empty, new
+    /// games may only be staked in listed tokens.
+    pub fn allow_token(env: Env, token: Address) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+
+        let mut tokens = allowed_tokens(&env);
+        if !tokens.contains(&token) {
+            tokens.push_back(token);
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::AllowedTokens, &tokens);
+        Ok(())
+    }
+
+    /// Remove a token from the stake allowlist. Games already staked in it
+    /// are unaffected; removing the last token allows any token again.
+    pub fn disallow_token(env: Env, token: Address) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+
+        let mut tokens = allowed_tokens(&env);
+        if let Some(index) = tokens.first_index_of(&token) {
+            tokens.remove(index);
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::AllowedTokens, &tokens);
+        Ok(())
+    }
+
+    pub fn get_allowed_tokens(env: Env) -> Vec<Address> {
+        allowed_tokens(&env)
+    }
+
+    /// Set where slashed stakes are sent.
+    pub fn set_treasury(env: Env, treasury: Address) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+        env.storage().instance().set(&DataKey::Treasury, &treasury);
+        Ok(())
+    }
+
+    pub fn get_treasury(env: Env) -> Option<Address> {
+        env.storage().instance().get(&DataKey::Treasury)
+    }
+
+    /// Share of a resigning player's stake, in basis points, slashed to the
+    /// treasury when a resigned game is paid out. The opponent receives the
+    /// rest of the pot. Has no effect until a treasury is set.
+    pub fn set_resign_penalty(env: Env, bps: u32) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+
+        if bps > BPS_DENOMINATOR {
+            return Err(Error::InvalidConfig);
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::ResignPenaltyBps, &bps);
+        Ok(())
+    }
+
+    pub fn get_resign_penalty(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::ResignPenaltyBps)
+            .unwrap_or(0)
+    }
+
+    /// Set or clear the observer notified after every move. Clears the
+    /// failure flag.
+    pub fn set_observer(env: Env, observer: Option<Address>) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+
+        match observer {
+            Some(observer) => env.storage().instance().set(&DataKey::Observer, &observer),
+            None => env.storage().instance().remove(&DataKey::Observer),
+        }
+        env.storage().instance().remove(&DataKey::ObserverFailed);
+        Ok(())
+    }
+
+    pub fn get_observer(env: Env) -> Option<Address> {
+        env.storage().instance().get(&DataKey::Observer)
+    }
+
+    /// Whether a notification has failed since the observer was last set.
+    pub fn observer_failed(env: Env) -> bool {
+        env.storage().instance().has(&DataKey::ObserverFailed)
+    }
+
+    /// Replace the timeout windows. Takes effect for every timeout evaluated
+    /// afterwards, including games already in progress.
+    pub fn set_timeout_config(env: Env, config: TimeoutConfig) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+
+        if config.move_timeout == 0
+            || config.feedback_timeout == 0
+            || config.abandonment_window == 0
+            || config.accept_window == 0
+        {
+            return Err(Error::InvalidConfig);
+        }
+
+        env.storage()
+            .instance()
+            .set(&DataKey::TimeoutConfig, &config);
+        Ok(())
+    }
+
+    /// One-shot backfill after upgrading from the original schema. Games
+    /// created before the player index existed cannot be discovered on-chain,
+    /// so the admin passes their session ids (e.g. collected off-chain).
+    /// Missing fields get the defaults those games were played under, and
+    /// ledger-based fields use the current ledger. Ids that are missing or
+    /// already current are skipped. Instance settings the constructor now
+    /// sets but the original deployment did not are filled with their
+    /// defaults. Returns the number of games migrated.
+    pub fn migrate(env: Env, session_ids: Vec<u32>) -> Result<u32, Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+
+        if env.storage().instance().has(&DataKey::Migrated) {
+            return Err(Error::AlreadyMigrated);
+        }
+
+        let instance = env.storage().instance();
+        if !instance.has(&DataKey::TimeoutConfig) {
+            instance.set(&DataKey::TimeoutConfig, &default_timeout_config());
+        }
+        if !instance.has(&DataKey::GameNamespace) {
+            instance.set(&DataKey::GameNamespace, &0u32);
+        }
+
+        let mut migrated = 0;
+        for session_id in session_ids.iter() {
+            let key = DataKey::Game(session_id);
+            let raw: Option<Val> = env.storage().temporary().get(&key);
+            let legacy = match raw.map(|val| LegacyGameState::try_from_val(&env, &val)) {
+                Some(Ok(legacy)) => legacy,
+                _ => continue,
+            };
+
+            let game = upgrade_legacy_game(&env, legacy);
+            save_game(&env, &key, &game);
+            index_game(&env, &game);
+            migrated += 1;
+        }
+
+        env.storage().instance().set(&DataKey::Migrated, &true);
+        Ok(migrated)
+    }
+
+    /// Permanently freeze the deployment: no new games, no admin changes and
+    /// no upgrades. Games already created can still be played out, settled
+    /// and reported.
+    pub fn seal(env: Env) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+
+        env.storage().instance().set(&DataKey::Sealed, &true);
+        Ok(())
+    }
+
+    pub fn is_sealed(env: Env) -> bool {
+        env.storage().instance().has(&DataKey::Sealed)
+    }
+
+    /// Stop or resume the creation of new games and tournaments. Games in
+    /// progress are unaffected.
+    pub fn set_paused(env: Env, paused: bool) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+
+        if paused {
+            env.storage().instance().set(&DataKey::Paused, &true);
+        } else {
+            env.storage().instance().remove(&DataKey::Paused);
+        }
+        Ok(())
+    }
+
+    pub fn get_status(env: Env) -> ContractStatus {
+        let storage = env.storage().instance();
+        let paused = storage.has(&DataKey::Paused);
+        let sealed = storage.has(&DataKey::Sealed);
+        ContractStatus {
+            paused,
+            sealed,
+            accepting_games: !paused && !sealed,
+            version: CONTRACT_VERSION,
+        }
+    }
+
+    /// Swap the contract wasm. Refused while any game holds escrow, so a
+    /// faulty upgrade cannot strand stakes, unless `force` is set.
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>, force: bool) -> Result<(), Error> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin not set");
+        admin.require_auth();
+        check_not_sealed(&env)?;
+
+        let outstanding = escrow_tokens(&env)
+            .iter()
+            .any(|token| total_escrow(&env, &token) != 0);
+        if outstanding && !force {
+            return Err(Error::EscrowOutstanding);
+        }
+
+        env.deployer().update_current_contract_wasm(new_wasm_hash);
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Helpers
+// ============================================================================
+
+fn default_config(env: &Env) -> GameConfig {
+    GameConfig {
+        code_length: CODE_LENGTH,
+        num_colors: NUM_COLORS,
+        max_guesses: MAX_GUESSES,
+        pipeline_depth: 1,
+        stake_token: None,
+        codemaker_stake: 0,
+        codebreaker_stake: 0,
+        draw_on_exhaustion: false,
+        commit_scheme: CommitScheme::Sha256,
+        trusted: false,
+        hints_allowed: false,
+        strict_proofs: false,
+        color_labels: Vec::new(env),
+        feedback_mode: FEEDBACK_SPLIT,
+        validity_proof: false,
+        sealed_feedback_allowed: false,
+        commit_deadline_ledgers: None,
+    }
+}
+
+fn validate_config(config: &GameConfig) -> Result<(), Error> {
+    if config.code_length == 0 || config.code_length > MAX_CODE_LENGTH {
+        return Err(Error::InvalidConfig);
+    }
+    if config.num_colors < 2 || config.num_colors > MAX_NUM_COLORS {
+        return Err(Error::InvalidConfig);
+    }
+    if config.max_guesses == 0 || config.max_guesses > MAX_GUESSES_LIMIT {
+        return Err(Error::InvalidConfig);
+    }
+    if config.pipeline_depth == 0 || config.pipeline_depth > MAX_PIPELINE_DEPTH {
+        return Err(Error::InvalidConfig);
+    }
+    // In a staked game each side puts up a positive stake; an unstaked one none at all
+    let stake_ok = match config.stake_token {
+        Some(_) => config.codemaker_stake > 0 && config.codebreaker_stake > 0,
+        None => config.codemaker_stake == 0 && config.codebreaker_stake == 0,
+    };
+    if !stake_ok {
+        return Err(Error::InvalidConfig);
+    }
+    if !config.color_labels.is_empty() && config.color_labels.len() != config.num_colors {
+        return Err(Error::InvalidConfig);
+    }
+    // Labels must tell colors apart, so each is named and no two share a name
+    for (index, label) in config.color_labels.iter().enumerate() {
+        if label.is_empty() {
+            return Err(Error::InvalidLabels);
+        }
+        for earlier in config.color_labels.iter().take(index) {
+            if earlier == label {
+                return Err(Error::InvalidLabels);
+            }
+        }
+    }
+    if config.feedback_mode != FEEDBACK_SPLIT && config.feedback_mode != FEEDBACK_COMBINED {
+        return Err(Error::InvalidConfig);
+    }
+    if config.commit_deadline_ledgers == Some(0) {
+        return Err(Error::InvalidConfig);
+    }
+    Ok(())
+}
+
+fn new_game_state(
+    env: &Env,
+    session_id: u32,
+    codemaker: Address,
+    codebreaker: Address,
+    config: &GameConfig,
+) -> GameState {
+    count_phase(env, &GamePhase::WaitingForAccept, 1);
+    GameState {
+        session_id,
+        codemaker,
+        codebreaker,
+        phase: GamePhase::WaitingForAccept,
+        commitment: BytesN::from_array(env, &[0u8; 32]),
+        guesses: Vec::new(env),
+        feedbacks: Vec::new(env),
+        guess_count: 0,
+        max_guesses: config.max_guesses,
+        winner: None,
+        current_guess: Vec::new(env),
+        code_length: config.code_length,
+        num_colors: config.num_colors,
+        created_ledger: env.ledger().sequence(),
+        finished_ledger: None,
+        randomness_anchor: randomness_anchor(env, session_id),
+        pipeline_depth: config.pipeline_depth,
+        pending_guesses: Vec::new(env),
+        stake_token: config.stake_token.clone(),
+        codemaker_stake: config.codemaker_stake,
+        codebreaker_stake: config.codebreaker_stake,
+        draw_on_exhaustion: config.draw_on_exhaustion,
+        settled: false,
+        phase_entered_ledger: env.ledger().sequence(),
+        revealed: false,
+        commit_scheme: config.commit_scheme,
+        finish_reason: FinishReason::Unfinished,
+        trusted: config.trusted,
+        hints_allowed: config.hints_allowed,
+        strict_proofs: config.strict_proofs,
+        color_labels: config.color_labels.clone(),
+        reported: false,
+        feedback_mode: config.feedback_mode,
+        hints: Vec::new(env),
+        guess_agent: None,
+        secret: Vec::new(env),
+        validity_proof: config.validity_proof,
+        sealed_feedback_allowed: config.sealed_feedback_allowed,
+        fair_play: FairPlay {
+            seed_hash: None,
+            seed_entropy: None,
+            validity_proof_hash: None,
+            sealed_feedback: None,
+            encrypted_secret: None,
+            decryption_key: None,
+            commit_deadline_ledgers: config.commit_deadline_ledgers,
+        },
+    }
+}
+
+fn upgrade_legacy_game(env: &Env, legacy: LegacyGameState) -> GameState {
+    let mut game = new_game_state(
+        env,
+        legacy.session_id,
+        legacy.codemaker,
+        legacy.codebreaker,
+        &default_config(env),
+    );
+    // The original flow held at most one unanswered guess
+    if legacy.phase == GamePhase::WaitingForFeedback {
+        game.pending_guesses.push_back(legacy.current_guess.clone());
+    }
+    if legacy.phase == GamePhase::Finished {
+        game.finished_ledger = Some(env.ledger().sequence());
+        // The original flow could only end by solving or exhausting guesses
+        game.finish_reason = if legacy.winner == Some(game.codebreaker.clone()) {
+            FinishReason::Solved
+        } else {
+            FinishReason::Exhausted
+        };
+    }
+    count_phase(env, &game.phase, -1);
+    count_phase(env, &legacy.phase, 1);
+    game.phase = legacy.phase;
+    game.commitment = legacy.commitment;
+    game.guesses = legacy.guesses;
+    game.feedbacks = legacy.feedbacks;
+    game.guess_count = legacy.guess_count;
+    game.max_guesses = legacy.max_guesses;
+    game.winner = legacy.winner;
+    game.current_guess = legacy.current_guess;
+    game
+}
+
+/// Audit anchor binding a game to the ledger it was created in:
+/// sha256(network_id || sequence || timestamp || session_id). Anyone can
+/// predict it, so it only feeds secret generation alongside PRNG output, as
+/// in `commit_seed` and `new_practice_game`.
+fn randomness_anchor(env: &Env, session_id: u32) -> BytesN<32> {
+    let mut data = Bytes::from_array(env, &env.ledger().network_id().to_array());
+    data.extend_from_array(&env.ledger().sequence().to_be_bytes());
+    data.extend_from_array(&env.ledger().timestamp().to_be_bytes());
+    data.extend_from_array(&session_id.to_be_bytes());
+    env.crypto().sha256(&data).into()
+}
+
+/// A revealed secret must respect the same shape as a guess.
+fn validate_secret(game: &GameState, secret: &Vec<u32>) -> Result<(), Error> {
+    validate_guess(game, secret).map_err(|_| Error::InvalidReveal)
+}
+
+fn commitment_for(
+    env: &Env,
+    scheme: CommitScheme,
+    secret: &Vec<u32>,
+    salt: &BytesN<32>,
+) -> BytesN<32> {
+    let mut preimage = Bytes::new(env);
+    for peg in secret.iter() {
+        preimage.extend_from_array(&peg.to_be_bytes());
+    }
+    preimage.extend_from_array(&salt.to_array());
+    match scheme {
+        CommitScheme::Sha256 => env.crypto().sha256(&preimage).into(),
+        CommitScheme::Keccak256 => env.crypto().keccak256(&preimage).into(),
+    }
+}
+
+/// Secret of a provably-fair game: peg `i` is byte `i` of
+/// sha256(seed || entropy), reduced modulo `num_colors`.
+fn seeded_secret(env: &Env, game: &GameState, seed: &BytesN<32>, entropy: &BytesN<32>) -> Vec<u32> {
+    let mut preimage = Bytes::from_array(env, &seed.to_array());
+    preimage.extend_from_array(&entropy.to_array());
+    let digest = env.crypto().sha256(&preimage).to_array();
+    let mut secret = Vec::new(env);
+    for i in 0..game.code_length {
+        secret.push_back(digest[i as usize] as u32 % game.num_colors);
+    }
+    secret
+}
+
+/// Public inputs of the feedback circuit for the guess awaiting feedback.
+fn feedback_public_inputs(
+    env: &Env,
+    game: &GameState,
+    correct_position: u32,
+    correct_color: u32,
+) -> Bytes {
+    let mut inputs = Bytes::from_array(env, &game.commitment.to_array());
+    let guess = game.pending_guesses.first().unwrap();
+    for value in guess.iter().chain([correct_position, correct_color]) {
+        inputs.extend_from_array(&u32_field(env, value).to_array());
+    }
+    inputs
+}
+
+/// `value` as a 32-byte big-endian circuit field.
+fn u32_field(env: &Env, value: u32) -> BytesN<32> {
+    let mut field = [0u8; 32];
+    field[28..].copy_from_slice(&value.to_be_bytes());
+    BytesN::from_array(env, &field)
+}
+
+/// A code as a native array; only the first `code_length` pegs are used.
+type Code = [u32; MAX_CODE_LENGTH as usize];
+
+/// Recorded guesses with their `(correct_position, correct_color)` feedback.
+type History = [(Code, (u32, u32)); MAX_GUESSES_LIMIT as usize];
+
+/// Copy the recorded guesses and feedback into native arrays for the solver
+/// helpers. Returns the number of entries used.
+fn feedback_history(game: &GameState) -> (History, usize) {
+    let mut history =
+        [([0u32; MAX_CODE_LENGTH as usize], (0u32, 0u32)); MAX_GUESSES_LIMIT as usize];
+    let mut answered = 0;
+    for (guess, feedback) in game.guesses.iter().zip(game.feedbacks.iter()) {
+        for (peg, color) in guess.iter().enumerate() {
+            history[answered].0[peg] = color;
+        }
+        history[answered].1 = (feedback.correct_position, feedback.correct_color);
+        answered += 1;
+    }
+    (history, answered)
+}
+
+/// The `index`-th code in lexicographic order, first peg most significant.
+fn nth_code(mut index: u32, code_length: u32, num_colors: u32) -> Code {
+    let mut code = [0u32; MAX_CODE_LENGTH as usize];
+    for peg in (0..code_length as usize).rev() {
+        code[peg] = index % num_colors;
+        index /= num_colors;
+    }
+    code
+}
+
+/// `(correct_position, correct_color)` pegs for `guess` against `secret`, or
+/// `(score, 0)` in `FEEDBACK_COMBINED` mode.
+fn compute_feedback(secret: &[u32], guess: &[u32], num_colors: u32, mode: u32) -> (u32, u32) {
+    let mut exact = 0;
+    let mut secret_counts = [0u32; MAX_NUM_COLORS as usize];
+    let mut guess_counts = [0u32; MAX_NUM_COLORS as usize];
+    for (s, g) in secret.iter().zip(guess.iter()) {
+        if s == g {
+            exact += 1;
+        }
+        secret_counts[*s as usize] += 1;
+        guess_counts[*g as usize] += 1;
+    }
+    let matches: u32 = (0..num_colors as usize)
+        .map(|color| secret_counts[color].min(guess_counts[color]))
+        .sum();
+    if mode == FEEDBACK_COMBINED {
+        (matches, 0)
+    } else {
+        (exact, matches - exact)
+    }
+}
+
+/// `code` would have produced every recorded feedback had it been the secret.
+fn is_consistent(code: &[u32], history: &[(Code, (u32, u32))], num_colors: u32, mode: u32) -> bool {
+    history.iter().all(|(guess, feedback)| {
+        compute_feedback(code, &guess[..code.len()], num_colors, mode) == *feedback
+    })
+}
+
+/// A practice game, whose codemaker is the contract itself.
+fn is_practice(env: &Env, game: &GameState) -> bool {
+    game.codemaker == env.current_contract_address()
+}
+
+/// The contract's own answer to the oldest pending guess of a practice game.
+fn practice_feedback(game: &GameState) -> (u32, u32) {
+    let mut secret = [0u32; MAX_CODE_LENGTH as usize];
+    let mut guess = [0u32; MAX_CODE_LENGTH as usize];
+    for (peg, color) in game.secret.iter().enumerate() {
+        secret[peg] = color;
+    }
+    for (peg, color) in game.pending_guesses.first_unchecked().iter().enumerate() {
+        guess[peg] = color;
+    }
+    let len = game.code_length as usize;
+    compute_feedback(
+        &secret[..len],
+        &guess[..len],
+        game.num_colors,
+        game.feedback_mode,
+    )
+}
+
+/// Whether every recorded feedback is what `secret` actually produces.
+fn feedbacks_match(game: &GameState, secret: &Vec<u32>) -> bool {
+    let mut code = [0u32; MAX_CODE_LENGTH as usize];
+    for (peg, color) in secret.iter().enumerate() {
+        code[peg] = color;
+    }
+    let len = game.code_length as usize;
+    let (history, answered) = feedback_history(game);
+    history[..answered]
+        .iter()
+        .enumerate()
+        .all(|(index, (guess, feedback))| {
+            compute_feedback(
+                &code[..len],
+                &guess[..len],
+                game.num_colors,
+                game.feedback_mode,
+            ) == *feedback
+                && solve_claim_holds(game, index as u32, guess[..len] == code[..len])
+        })
+}
+
+/// Whether the game's record of solving at guess `index` (only ever the last
+/// answered guess) agrees with whether that guess really was the secret.
+fn solve_claim_holds(game: &GameState, index: u32, is_secret: bool) -> bool {
+    let claimed = game.finish_reason == FinishReason::Solved && index + 1 == game.feedbacks.len();
+    claimed == is_secret
+}
+
+/// The candidate whose worst-case feedback leaves the fewest candidates.
+/// Ties go to the earliest candidate.
+fn minimax_guess(candidates: &[Code], len: usize, num_colors: u32, mode: u32) -> Code {
+    let mut best = candidates[0];
+    let mut best_worst = usize::MAX;
+    for guess in candidates {
+        // Bucket the candidates by the feedback this guess would get
+        let mut buckets = [0usize; (MAX_CODE_LENGTH as usize + 1) * (MAX_CODE_LENGTH as usize + 1)];
+        for secret in candidates {
+            let (exact, near) = compute_feedback(&secret[..len], &guess[..len], num_colors, mode);
+            buckets[exact as usize * (len + 1) + near as usize] += 1;
+        }
+        let worst = buckets.iter().copied().max().unwrap_or(0);
+        if worst < best_worst {
+            best = *guess;
+            best_worst = worst;
+        }
+    }
+    best
+}
+
+/// Number of pegs of each color in `code`, indexed by color.
+fn color_counts(env: &Env, code: &Vec<u32>, num_colors: u32) -> Vec<u32> {
+    let mut counts = Vec::new(env);
+    for _ in 0..num_colors {
+        counts.push_back(0u32);
+    }
+    for color in code.iter() {
+        counts.set(color, counts.get(color).unwrap() + 1);
+    }
+    counts
+}
+
+/// Move to `phase`, recording when it was entered. Re-entering the current
+/// phase (e.g. queueing a pipelined guess) keeps the original ledger.
+fn set_phase(env: &Env, game: &mut GameState, phase: GamePhase) {
+    if game.phase != phase {
+        record_phase(env, game, phase.clone());
+        count_phase(env, &game.phase, -1);
+        count_phase(env, &phase, 1);
+        game.phase = phase;
+        game.phase_entered_ledger = env.ledger().sequence();
+    }
+}
+
+/// A game, from persistent storage if a player persisted it, otherwise from
+/// temporary storage.
+fn load_game(env: &Env, key: &DataKey) -> Option<GameState> {
+    env.storage()
+        .persistent()
+        .get(key)
+        .or_else(|| env.storage().temporary().get(key))
+}
+
+/// Write a game back to whichever storage it lives in, extending its TTL.
+fn save_game(env: &Env, key: &DataKey, game: &GameState) {
+    if env.storage().persistent().has(key) {
+        env.storage().persistent().set(key, game);
+        env.storage().persistent().extend_ttl(
+            key,
+            PERSISTENT_GAME_TTL_LEDGERS,
+            PERSISTENT_GAME_TTL_LEDGERS,
+        );
+    } else {
+        env.storage().temporary().set(key, game);
+        env.storage()
+            .temporary()
+            .extend_ttl(key, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+    }
+}
+
+fn remove_game(env: &Env, key: &DataKey) {
+    env.storage().persistent().remove(key);
+    env.storage().temporary().remove(key);
+}
+
+fn phase_counts(env: &Env) -> PhaseCounts {
+    env.storage()
+        .persistent()
+        .get(&DataKey::PhaseCounts)
+        .unwrap_or_default()
+}
+
+/// Move the per-phase game counters by `delta` for `phase`.
+fn count_phase(env: &Env, phase: &GamePhase, delta: i32) {
+    let mut counts = phase_counts(env);
+    let count = match phase {
+        GamePhase::WaitingForAccept => &mut counts.waiting_for_accept,
+        GamePhase::WaitingForCommitment => &mut counts.waiting_for_commitment,
+        GamePhase::WaitingForGuess => &mut counts.waiting_for_guess,
+        GamePhase::WaitingForFeedback => &mut counts.waiting_for_feedback,
+        GamePhase::Finished => &mut counts.finished,
+    };
+    *count = count.saturating_add_signed(delta);
+    env.storage()
+        .persistent()
+        .set(&DataKey::PhaseCounts, &counts);
+    env.storage().persistent().extend_ttl(
+        &DataKey::PhaseCounts,
+        GAME_TTL_LEDGERS,
+        GAME_TTL_LEDGERS,
+    );
+}
+
+fn player_stats(env: &Env, player: &Address) -> PlayerStats {
+    env.storage()
+        .persistent()
+        .get(&DataKey::PlayerStats(player.clone()))
+        .unwrap_or_default()
+}
+
+/// Move `player`'s clean-reveal and caught-lie counts and recompute honesty.
+fn adjust_stats(env: &Env, player: &Address, clean: i32, caught: i32) {
+    let mut stats = player_stats(env, player);
+    stats.clean_reveals = stats.clean_reveals.saturating_add_signed(clean);
+    stats.caught_lies = stats.caught_lies.saturating_add_signed(caught);
+    stats.honesty = stats.clean_reveals as i32 - stats.caught_lies as i32;
+
+    let key = DataKey::PlayerStats(player.clone());
+    env.storage().persistent().set(&key, &stats);
+    env.storage()
+        .persistent()
+        .extend_ttl(&key, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+}
+
+fn phase_history(env: &Env, game: &GameState) -> Vec<GamePhase> {
+    env.storage()
+        .temporary()
+        .get(&DataKey::PhaseHistory(game.session_id))
+        .unwrap_or_else(|| Vec::from_array(env, [game.phase.clone()]))
+}
+
+/// Append `phase` to the game's phase history, dropping the oldest entry
+/// once `PHASE_HISTORY_LIMIT` is reached.
+fn record_phase(env: &Env, game: &GameState, phase: GamePhase) {
+    let mut history = phase_history(env, game);
+    if history.len() >= PHASE_HISTORY_LIMIT {
+        history.pop_front();
+    }
+    history.push_back(phase);
+    let key = DataKey::PhaseHistory(game.session_id);
+    env.storage().temporary().set(&key, &history);
+    env.storage()
+        .temporary()
+        .extend_ttl(&key, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+}
+
+/// Every mutating entrypoint checks its required phase through here so the
+/// phase machine is enforced in one place.
+fn assert_phase(game: &GameState, expected: GamePhase) -> Result<(), Error> {
+    assert_phase_in(game, &[expected])
+}
+
+fn assert_phase_in(game: &GameState, allowed: &[GamePhase]) -> Result<(), Error> {
+    if allowed.contains(&game.phase) {
+        Ok(())
+    } else {
+        Err(Error::InvalidPhase)
+    }
+}
+
+/// Unanswered pipelined guesses are dropped once the game is decided.
+/// A `None` winner is a draw.
+fn finish_game(env: &Env, game: &mut GameState, winner: Option<Address>, reason: FinishReason) {
+    set_phase(env, game, GamePhase::Finished);
+    game.finish_reason = reason;
+    game.pending_guesses = Vec::new(env);
+    game.winner = winner;
+    game.finished_ledger = Some(env.ledger().sequence());
+    unindex_active_game(env, game.session_id);
+    append_history(env, result_hash(env, game));
+}
+
+/// Award an already finished game to the codebreaker after all. The history
+/// gets a correction leaf, so the latest leaf for a session is its outcome.
+fn overturn_result(env: &Env, game: &mut GameState, reason: FinishReason) {
+    game.winner = Some(game.codebreaker.clone());
+    game.finish_reason = reason;
+    append_history(env, result_hash(env, game));
+}
+
+fn result_hash(env: &Env, game: &GameState) -> BytesN<32> {
+    let outcome = (
+        game.session_id,
+        game.winner.clone(),
+        game.guess_count,
+        game.commitment.clone(),
+        game.feedbacks.last(),
+    );
+    env.crypto().sha256(&outcome.to_xdr(env)).into()
+}
+
+fn hash_pair(env: &Env, left: &BytesN<32>, right: &BytesN<32>) -> BytesN<32> {
+    let mut preimage = Bytes::from_array(env, &left.to_array());
+    preimage.extend_from_array(&right.to_array());
+    env.crypto().sha256(&preimage).into()
+}
+
+/// Add a finished game to the history range and refresh its root.
+fn append_history(env: &Env, leaf: BytesN<32>) {
+    let storage = env.storage().persistent();
+    let count: u32 = storage.get(&DataKey::HistoryCount).unwrap_or(0);
+    let mut peaks: Vec<BytesN<32>> = storage.get(&DataKey::HistoryPeaks).unwrap_or(Vec::new(env));
+
+    // Each trailing one bit of the old count is a peak of equal height
+    let mut node = leaf;
+    let mut n = count;
+    while n & 1 == 1 {
+        node = hash_pair(env, &peaks.pop_back().unwrap(), &node);
+        n >>= 1;
+    }
+    peaks.push_back(node);
+
+    let mut root = peaks.last().unwrap();
+    for i in (0..peaks.len() - 1).rev() {
+        root = hash_pair(env, &peaks.get_unchecked(i), &root);
+    }
+
+    storage.set(&DataKey::HistoryCount, &(count + 1));
+    storage.set(&DataKey::HistoryPeaks, &peaks);
+    storage.set(&DataKey::HistoryRoot, &root);
+    for key in [
+        DataKey::HistoryCount,
+        DataKey::HistoryPeaks,
+        DataKey::HistoryRoot,
+    ] {
+        storage.extend_ttl(&key, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+    }
+}
+
+/// Apply feedback to the oldest pending guess, once the caller has been
+/// authorized. Game Hub reporting is done via a separate `report_result` call.
+/// `exact` is the codemaker's word that the guess is the secret. Only a
+/// combined score needs it to tell a win from a permutation; split feedback
+/// shows a win as every peg in the correct position.
+fn record_feedback(
+    env: &Env,
+    session_id: u32,
+    codemaker: Address,
+    correct_position: u32,
+    correct_color: u32,
+    exact: bool,
+    proof_hash: BytesN<32>,
+) -> Result<(), Error> {
+    let key = DataKey::Game(session_id);
+    let mut game: GameState = load_game(env, &key).ok_or(Error::GameNotFound)?;
+
+    assert_phase(&game, GamePhase::WaitingForFeedback)?;
+    if game.codemaker != codemaker {
+        return Err(Error::NotCodeMaker);
+    }
+
+    // Validate feedback bounds
+    if correct_position > game.code_length
+        || correct_color > game.code_length
+        || correct_position + correct_color > game.code_length
+    {
+        return Err(Error::InvalidFeedback);
+    }
+    // A combined score has no white pegs
+    if game.feedback_mode == FEEDBACK_COMBINED && correct_color != 0 {
+        return Err(Error::InvalidFeedback);
+    }
+    if exact && correct_position != game.code_length {
+        return Err(Error::InvalidFeedback);
+    }
+
+    // An all-zero hash means the client never attached a proof
+    if !game.trusted && proof_hash == BytesN::from_array(env, &[0u8; 32]) {
+        return Err(Error::MissingProof);
+    }
+    // A proof answers one guess; a repeated hash means a copied proof
+    if game.strict_proofs && has_seen_proof(&game, &proof_hash) {
+        return Err(Error::DuplicateProof);
+    }
+
+    let feedback = Feedback {
+        correct_position,
+        correct_color,
+        proof_hash: proof_hash.clone(),
+    };
+
+    // Feedback always answers the oldest pending guess
+    let guess = game.pending_guesses.pop_front().unwrap();
+    game.guesses.push_back(guess);
+    game.fair_play.sealed_feedback = None;
+    game.feedbacks.push_back(feedback);
+    game.guess_count += 1;
+
+    // Check win: every peg in the correct position, which a combined score
+    // cannot show on its own
+    let solved = if game.feedback_mode == FEEDBACK_COMBINED {
+        exact
+    } else {
+        correct_position == game.code_length
+    };
+    let exhausted = game.guess_count >= guess_limit(&game);
+    let phase = if solved || exhausted {
+        GamePhase::Finished
+    } else if game.pending_guesses.is_empty() {
+        GamePhase::WaitingForGuess
+    } else {
+        GamePhase::WaitingForFeedback
+    };
+    FeedbackGiven {
+        session_id,
+        correct_position,
+        correct_color,
+        proof_hash,
+        phase,
+    }
+    .publish(env);
+    log_event(env, EventKind::Feedback, session_id);
+    if !solved && game.guess_count + 1 == guess_limit(&game) {
+        LastChance { session_id }.publish(env);
+        log_event(env, EventKind::LastChance, session_id);
+    }
+
+    if solved {
+        let winner = game.codebreaker.clone();
+        finish_game(env, &mut game, Some(winner.clone()), FinishReason::Solved);
+        GameSolved {
+            session_id,
+            loser: opponent(&game, &winner),
+            winner,
+        }
+        .publish(env);
+        log_event(env, EventKind::Solved, session_id);
+    } else if exhausted && game.draw_on_exhaustion {
+        finish_game(env, &mut game, None, FinishReason::Draw);
+        GameDrawn {
+            session_id,
+            codemaker: game.codemaker.clone(),
+            codebreaker: game.codebreaker.clone(),
+        }
+        .publish(env);
+        log_event(env, EventKind::Drawn, session_id);
+    } else if exhausted {
+        let winner = game.codemaker.clone();
+        finish_game(
+            env,
+            &mut game,
+            Some(winner.clone()),
+            FinishReason::Exhausted,
+        );
+        GameExhausted {
+            session_id,
+            loser: opponent(&game, &winner),
+            winner,
+        }
+        .publish(env);
+        log_event(env, EventKind::Exhausted, session_id);
+    } else if let Some(next) = game.pending_guesses.first() {
+        game.current_guess = next;
+    } else {
+        set_phase(env, &mut game, GamePhase::WaitingForGuess);
+    }
+
+    save_game(env, &key, &game);
+    notify_observer(env, &game);
+
+    Ok(())
+}
+
+/// Guesses the codebreaker may use, after the cost of any hints.
+fn guess_limit(game: &GameState) -> u32 {
+    game.max_guesses - game.hints.len()
+}
+
+fn has_seen_proof(game: &GameState, proof_hash: &BytesN<32>) -> bool {
+    game.feedbacks.iter().any(|f| f.proof_hash == *proof_hash)
+}
+
+/// Guesses the codebreaker can still submit; 0 once the game is decided.
+fn remaining_guesses(game: &GameState) -> u32 {
+    if game.phase == GamePhase::Finished {
+        return 0;
+    }
+    guess_limit(game) - game.guess_count - game.pending_guesses.len()
+}
+
+/// A guess is canonical when it has exactly `code_length` pegs and every
+/// color index is below `num_colors`.
+fn validate_guess(game: &GameState, guess: &Vec<u32>) -> Result<(), Error> {
+    if guess.len() != game.code_length {
+        return Err(Error::InvalidGuessValue);
+    }
+    for val in guess.iter() {
+        if val >= game.num_colors {
+            return Err(Error::InvalidGuessValue);
+        }
+    }
+    Ok(())
+}
+
+/// The other participant of `game`, e.g. the loser given the winner.
+fn opponent(game: &GameState, player: &Address) -> Address {
+    if *player == game.codemaker {
+        game.codebreaker.clone()
+    } else {
+        game.codemaker.clone()
+    }
+}
+
+fn player_games(env: &Env, player: &Address) -> Vec<u32> {
+    env.storage()
+        .persistent()
+        .get(&DataKey::PlayerGames(player.clone()))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Record a new game in both players' indexes and, unless it is already
+/// over, the active-game index.
+fn index_game(env: &Env, game: &GameState) {
+    index_player_game(env, &game.codemaker, game.session_id);
+    index_player_game(env, &game.codebreaker, game.session_id);
+    if game.phase != GamePhase::Finished {
+        index_active_game(env, game.session_id);
+    }
+}
+
+fn active_games_index(env: &Env) -> Vec<u32> {
+    env.storage()
+        .persistent()
+        .get(&DataKey::ActiveGames)
+        .unwrap_or(Vec::new(env))
+}
+
+fn save_active_games_index(env: &Env, games: &Vec<u32>) {
+    env.storage().persistent().set(&DataKey::ActiveGames, games);
+    env.storage().persistent().extend_ttl(
+        &DataKey::ActiveGames,
+        GAME_TTL_LEDGERS,
+        GAME_TTL_LEDGERS,
+    );
+}
+
+/// Add `session_id` to the active-game index, dropping the oldest entry once
+/// `ACTIVE_INDEX_LIMIT` is reached.
+fn index_active_game(env: &Env, session_id: u32) {
+    let mut games = active_games_index(env);
+    if games.contains(session_id) {
+        return;
+    }
+    if games.len() >= ACTIVE_INDEX_LIMIT {
+        games.pop_front();
+    }
+    games.push_back(session_id);
+    save_active_games_index(env, &games);
+}
+
+fn unindex_active_game(env: &Env, session_id: u32) {
+    let mut games = active_games_index(env);
+    if let Some(i) = games.first_index_of(session_id) {
+        games.remove(i);
+        save_active_games_index(env, &games);
+    }
+}
+
+fn event_log(env: &Env) -> Vec<EventRecord> {
+    env.storage()
+        .persistent()
+        .get(&DataKey::EventLog)
+        .unwrap_or(Vec::new(env))
+}
+
+/// Append to the recent-events buffer, dropping the oldest record once
+/// `EVENT_LOG_LIMIT` is reached.
+fn log_event(env: &Env, kind: EventKind, session_id: u32) {
+    let mut log = event_log(env);
+    if log.len() >= EVENT_LOG_LIMIT {
+        log.pop_front();
+    }
+    log.push_back(EventRecord {
+        kind,
+        session_id,
+        ledger: env.ledger().sequence(),
+    });
+    env.storage().persistent().set(&DataKey::EventLog, &log);
+    env.storage()
+        .persistent()
+        .extend_ttl(&DataKey::EventLog, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+}
+
+/// Record `session_id` in the player's index, dropping the oldest entry once
+/// `PLAYER_INDEX_LIMIT` is reached so scans stay bounded.
+fn index_player_game(env: &Env, player: &Address, session_id: u32) {
+    let mut games = player_games(env, player);
+    if games.contains(session_id) {
+        return;
+    }
+    if games.len() >= PLAYER_INDEX_LIMIT {
+        games.pop_front();
+    }
+    games.push_back(session_id);
+
+    let key = DataKey::PlayerGames(player.clone());
+    env.storage().persistent().set(&key, &games);
+    env.storage()
+        .persistent()
+        .extend_ttl(&key, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+}
+
+/// Pair `players` into the tournament's next round of matches. Matches count
+/// toward the per-player game cap like any other game, so a round cannot
+/// start while a player is at the cap.
+fn start_round(
+    env: &Env,
+    tournament_id: u32,
+    tournament: &mut Tournament,
+    players: &Vec<Address>,
+) -> Result<(), Error> {
+    let config = default_config(env);
+    let mut matches = Vec::new(env);
+    for i in (0..players.len()).step_by(2) {
+        let (codemaker, codebreaker) = (players.get_unchecked(i), players.get_unchecked(i + 1));
+        check_game_limit(env, &codemaker)?;
+        check_game_limit(env, &codebreaker)?;
+        let session_id = next_match_id(env)?;
+        let game = new_game_state(env, session_id, codemaker, codebreaker, &config);
+
+        let key = DataKey::Game(session_id);
+        save_game(env, &key, &game);
+        index_game(env, &game);
+        notify_observer(env, &game);
+        matches.push_back(session_id);
+    }
+
+    tournament.round += 1;
+    tournament.matches = matches.clone();
+    tournament.entrants = players.clone();
+    tournament.walkovers = Map::new(env);
+    save_tournament(env, tournament_id, tournament);
+    TournamentRound {
+        tournament_id,
+        round: tournament.round,
+        matches,
+    }
+    .publish(env);
+
+    Ok(())
+}
+
+fn save_tournament(env: &Env, tournament_id: u32, tournament: &Tournament) {
+    let key = DataKey::Tournament(tournament_id);
+    env.storage().persistent().set(&key, tournament);
+    env.storage()
+        .persistent()
+        .extend_ttl(&key, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+}
+
+fn save_dual_game(env: &Env, dual_id: u32, dual: &DualGame) {
+    let key = DataKey::DualGame(dual_id);
+    env.storage().persistent().set(&key, dual);
+    env.storage()
+        .persistent()
+        .extend_ttl(&key, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+}
+
+/// A dual game session from its codebreaker's side, with the guesses they
+/// needed or have used so far. `Forfeited` means its codemaker lost it
+/// without the code being solved; `Void` that it no longer exists.
+enum DualProgress {
+    Solved(u32),
+    Playing(u32),
+    Failed,
+    Forfeited,
+    Void,
+}
+
+fn dual_progress(game: Option<GameState>) -> DualProgress {
+    let Some(game) = game else {
+        return DualProgress::Void;
+    };
+    if game.phase != GamePhase::Finished {
+        DualProgress::Playing(game.guess_count)
+    } else if game.finish_reason == FinishReason::Solved {
+        DualProgress::Solved(game.guess_count)
+    } else if game.winner == Some(game.codebreaker) {
+        DualProgress::Forfeited
+    } else {
+        DualProgress::Failed
+    }
+}
+
+/// Next free session id for a contract-created match, skipping any id a
+/// player has already used.
+fn next_match_id(env: &Env) -> Result<u32, Error> {
+    let mut session_id: u32 = env
+        .storage()
+        .instance()
+        .get(&DataKey::NextMatchId)
+        .unwrap_or(1);
+    while load_game(env, &DataKey::Game(session_id)).is_some() {
+        session_id += 1;
+    }
+    check_session_id(env, session_id)?;
+    env.storage()
+        .instance()
+        .set(&DataKey::NextMatchId, &(session_id + 1));
+    Ok(session_id)
+}
+
+fn verifier<'a>(env: &'a Env, game: &GameState) -> VerifierClient<'a> {
+    let verifier_addr = verifier_address(env, game.code_length, game.num_colors);
+    VerifierClient::new(env, &verifier_addr)
+}
+
+/// Check a feedback proof, passing the stored verification key when the
+/// game uses the global verifier and one has been set.
+fn verify_feedback_proof(
+    env: &Env,
+    game: &GameState,
+    public_inputs: &Bytes,
+    proof: &Bytes,
+) -> bool {
+    let verifier = verifier(env, game);
+    let per_rules = DataKey::VerifierFor(game.code_length, game.num_colors);
+    if env.storage().instance().has(&per_rules) {
+        return verifier.verify_proof(public_inputs, proof);
+    }
+    let key = DataKey::VerificationKey;
+    match env.storage().persistent().get::<_, Bytes>(&key) {
+        Some(vk) => {
+            env.storage()
+                .persistent()
+                .extend_ttl(&key, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+            verifier.verify_proof_with_vk(&vk, public_inputs, proof)
+        }
+        None => verifier.verify_proof(public_inputs, proof),
+    }
+}
+
+fn verifier_address(env: &Env, code_length: u32, num_colors: u32) -> Address {
+    let storage = env.storage().instance();
+    storage
+        .get(&DataKey::VerifierFor(code_length, num_colors))
+        .unwrap_or_else(|| {
+            storage
+                .get(&DataKey::VerifierAddress)
+                .expect("Verifier not set")
+        })
+}
+
+fn allowed_tokens(env: &Env) -> Vec<Address> {
+    env.storage()
+        .instance()
+        .get(&DataKey::AllowedTokens)
+        .unwrap_or(Vec::new(env))
+}
+
+fn check_token_allowed(env: &Env, stake_token: &Option<Address>) -> Result<(), Error> {
+    let allowed = allowed_tokens(env);
+    match stake_token {
+        Some(token) if !allowed.is_empty() && !allowed.contains(token) => {
+            Err(Error::TokenNotAllowed)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn max_games_per_player(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&DataKey::MaxGamesPerPlayer)
+        .unwrap_or(DEFAULT_MAX_GAMES_PER_PLAYER)
+}
+
+/// Unfinished games among the player's indexed sessions.
+fn active_games(env: &Env, player: &Address) -> u32 {
+    let mut active = 0;
+    for session_id in player_games(env, player).iter() {
+        let game = load_game(env, &DataKey::Game(session_id));
+        if game.is_some_and(|game| game.phase != GamePhase::Finished) {
+            active += 1;
+        }
+    }
+    active
+}
+
+fn check_game_limit(env: &Env, player: &Address) -> Result<(), Error> {
+    if active_games(env, player) >= max_games_per_player(env) {
+        return Err(Error::TooManyActiveGames);
+    }
+    Ok(())
+}
+
+fn game_hub(env: &Env) -> GameHubClient<'_> {
+    let game_hub_addr: Address = env
+        .storage()
+        .instance()
+        .get(&DataKey::GameHubAddress)
+        .expect("GameHub not set");
+    GameHubClient::new(env, &game_hub_addr)
+}
+
+fn game_namespace(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&DataKey::GameNamespace)
+        .unwrap_or(0)
+}
+
+fn check_not_sealed(env: &Env) -> Result<(), Error> {
+    if env.storage().instance().has(&DataKey::Sealed) {
+        return Err(Error::Sealed);
+    }
+    Ok(())
+}
+
+fn check_accepting_games(env: &Env) -> Result<(), Error> {
+    check_not_sealed(env)?;
+    if env.storage().instance().has(&DataKey::Paused) {
+        return Err(Error::Paused);
+    }
+    Ok(())
+}
+
+/// Namespaced deployments fold the namespace into the top byte of the hub
+/// session id, so local ids must fit in the remaining 24 bits.
+fn check_session_id(env: &Env, session_id: u32) -> Result<(), Error> {
+    if game_namespace(env) != 0 && session_id > MAX_NAMESPACED_SESSION_ID {
+        return Err(Error::InvalidSessionId);
+    }
+    Ok(())
+}
+
+/// Open a finished game's commitment, shared by `reveal_code` and
+/// `reveal_seed`. Checks the secret against the commitment and, in a
+/// `strict_proofs` game, every recorded feedback against the secret.
+fn open_commitment(
+    env: &Env,
+    session_id: u32,
+    codemaker: Address,
+    secret: Vec<u32>,
+    salt: BytesN<32>,
+) -> Result<(), Error> {
+    let key = DataKey::Game(session_id);
+    let mut game: GameState = load_game(env, &key).ok_or(Error::GameNotFound)?;
+
+    assert_phase(&game, GamePhase::Finished)?;
+    if game.codemaker != codemaker {
+        return Err(Error::NotCodeMaker);
+    }
+    if game.revealed {
+        return Err(Error::AlreadyRevealed);
+    }
+    // Past the dispute window an unrevealed win is the codebreaker's to claim
+    if awaiting_reveal(env, &game) && !dispute_window_open(env, &game) {
+        return Err(Error::InvalidPhase);
+    }
+
+    validate_secret(&game, &secret)?;
+    if commitment_for(env, game.commit_scheme, &secret, &salt) != game.commitment {
+        return Err(Error::CommitmentMismatch);
+    }
+
+    game.revealed = true;
+    game.secret = secret.clone();
+    let caught = game.strict_proofs && !feedbacks_match(&game, &secret);
+    if caught {
+        overturn_result(env, &mut game, FinishReason::FalseFeedback);
+    }
+
+    save_game(env, &key, &game);
+    notify_observer(env, &game);
+    if caught {
+        adjust_stats(env, &codemaker, 0, 1);
+    } else {
+        adjust_stats(env, &codemaker, 1, 0);
+    }
+
+    CodeRevealed { session_id, secret }.publish(env);
+    log_event(env, EventKind::Revealed, session_id);
+    if caught {
+        GameForfeited {
+            session_id,
+            winner: game.codebreaker,
+            loser: codemaker,
+        }
+        .publish(env);
+        log_event(env, EventKind::Forfeited, session_id);
+    }
+
+    Ok(())
+}
+
+/// A live game must never be overwritten: it may hold escrowed stakes.
+fn check_session_free(env: &Env, session_id: u32) -> Result<(), Error> {
+    if load_game(env, &DataKey::Game(session_id)).is_some() {
+        return Err(Error::GameAlreadyExists);
+    }
+    Ok(())
+}
+
+/// Session id as seen by the Game Hub. Namespace 0 leaves ids unchanged.
+fn hub_session_id(env: &Env, session_id: u32) -> u32 {
+    (game_namespace(env) << NAMESPACE_SHIFT) | session_id
+}
+
+/// `report_to_hub` that returns an error instead of trapping: `HubRejected`
+/// if the hub returned a contract error of its own, `HubCallFailed` if the
+/// call itself failed.
+fn try_report_to_hub(env: &Env, game: &GameState) -> Result<(), Error> {
+    let hub: Address = env
+        .storage()
+        .instance()
+        .get(&DataKey::GameHubAddress)
+        .ok_or(Error::HubNotSet)?;
+    let codemaker_won = game.winner == Some(game.codemaker.clone());
+    match GameHubClient::new(env, &hub)
+        .try_end_game(&hub_session_id(env, game.session_id), &codemaker_won)
+    {
+        Ok(Ok(())) => Ok(()),
+        Err(Ok(error)) if error.is_type(ScErrorType::Contract) => Err(Error::HubRejected),
+        _ => Err(Error::HubCallFailed),
+    }
+}
+
+fn register_with_hub(env: &Env, game: &GameState) {
+    game_hub(env).start_game(
+        &env.current_contract_address(),
+        &hub_session_id(env, game.session_id),
+        &game.codemaker,
+        &game.codebreaker,
+        &player_points(env, &game.codemaker),
+        &player_points(env, &game.codebreaker),
+    );
+}
+
+/// Tell the observer, if any, about the move just saved. A failing observer
+/// only raises `ObserverFailed`; it never reverts the move.
+///
+/// Every change to a game's own state is reported. A cancelled game is
+/// reported in the phase it was cancelled from, after which it no longer
+/// exists. Hub reporting, payouts, `persist_game` and `migrate` only touch
+/// bookkeeping or storage, so they are not reported.
+fn notify_observer(env: &Env, game: &GameState) {
+    let observer: Option<Address> = env.storage().instance().get(&DataKey::Observer);
+    if let Some(observer) = observer {
+        let result = ObserverClient::new(env, &observer).try_on_move(
+            &game.session_id,
+            &game.phase,
+            &game.guess_count,
+        );
+        if !matches!(result, Ok(Ok(()))) {
+            env.storage()
+                .instance()
+                .set(&DataKey::ObserverFailed, &true);
+        }
+    }
+}
+
+fn player_points(env: &Env, player: &Address) -> i128 {
+    let ratings: Option<Address> = env.storage().instance().get(&DataKey::RatingsAddress);
+    match ratings {
+        Some(ratings) => RatingsClient::new(env, &ratings).get_player_points(player),
+        None => 0,
+    }
+}
+
+/// Escrow `player`'s stake into the contract. No-op for unstaked games.
+fn deposit_stake(env: &Env, game: &GameState, player: &Address) {
+    if let Some(token) = &game.stake_token {
+        let amount = stake_of(game, player);
+        let contract = env.current_contract_address();
+        TokenClient::new(env, token).transfer(player, &contract, &amount);
+        adjust_escrow(env, token, amount);
+    }
+}
+
+/// What `player` puts up in this game.
+fn stake_of(game: &GameState, player: &Address) -> i128 {
+    if *player == game.codemaker {
+        game.codemaker_stake
+    } else {
+        game.codebreaker_stake
+    }
+}
+
+/// Both stakes combined.
+fn pot(game: &GameState) -> i128 {
+    game.codemaker_stake
+        .checked_add(game.codebreaker_stake)
+        .expect("Stake overflow")
+}
+
+/// `player`'s share of a finished game's escrow.
+fn payout_for(env: &Env, game: &GameState, player: &Address) -> i128 {
+    match &game.winner {
+        Some(winner) if winner == player => {
+            pot(game) - resign_penalty(env, game).map_or(0, |(_, penalty)| penalty)
+        }
+        Some(_) => 0,
+        None if *player == game.codemaker || *player == game.codebreaker => stake_of(game, player),
+        None => 0,
+    }
+}
+
+/// Release a finished game's escrow: the pot to the winner, less any
+/// resignation penalty, or each stake back to its owner on a draw.
+fn pay_out(env: &Env, game: &GameState, token: &Address) {
+    match &game.winner {
+        Some(winner) => {
+            let mut winnings = pot(game);
+            if let Some((treasury, penalty)) = resign_penalty(env, game) {
+                release_escrow(env, token, &treasury, penalty);
+                winnings -= penalty;
+            }
+            release_escrow(env, token, winner, winnings);
+        }
+        None => {
+            release_escrow(env, token, &game.codemaker, game.codemaker_stake);
+            release_escrow(env, token, &game.codebreaker, game.codebreaker_stake);
+        }
+    }
+}
+
+/// The treasury and the share of the loser's stake it takes when a game was
+/// resigned. `None` when there is nothing to slash.
+fn resign_penalty(env: &Env, game: &GameState) -> Option<(Address, i128)> {
+    if game.finish_reason != FinishReason::Resigned {
+        return None;
+    }
+    let winner = game.winner.as_ref()?;
+    let treasury: Address = env.storage().instance().get(&DataKey::Treasury)?;
+    let bps: u32 = env
+        .storage()
+        .instance()
+        .get(&DataKey::ResignPenaltyBps)
+        .unwrap_or(0);
+    let penalty = stake_of(game, &opponent(game, winner))
+        .checked_mul(bps as i128)
+        .expect("Stake overflow")
+        / BPS_DENOMINATOR as i128;
+    if penalty == 0 {
+        return None;
+    }
+    Some((treasury, penalty))
+}
+
+/// Pay `amount` of escrowed `token` out to `to`.
+fn release_escrow(env: &Env, token: &Address, to: &Address, amount: i128) {
+    adjust_escrow(env, token, -amount);
+    TokenClient::new(env, token).transfer(&env.current_contract_address(), to, &amount);
+}
+
+fn total_escrow(env: &Env, token: &Address) -> i128 {
+    env.storage()
+        .persistent()
+        .get(&DataKey::Escrow(token.clone()))
+        .unwrap_or(0)
+}
+
+/// Every token that has ever been escrowed.
+fn escrow_tokens(env: &Env) -> Vec<Address> {
+    env.storage()
+        .persistent()
+        .get(&DataKey::EscrowTokens)
+        .unwrap_or(Vec::new(env))
+}
+
+fn adjust_escrow(env: &Env, token: &Address, delta: i128) {
+    let mut tokens = escrow_tokens(env);
+    if !tokens.contains(token) {
+        tokens.push_back(token.clone());
+    }
+    env.storage()
+        .persistent()
+        .set(&DataKey::EscrowTokens, &tokens);
+    env.storage().persistent().extend_ttl(
+        &DataKey::EscrowTokens,
+        GAME_TTL_LEDGERS,
+        GAME_TTL_LEDGERS,
+    );
+
+    let total = total_escrow(env, token)
+        .checked_add(delta)
+        .expect("Escrow overflow");
+    let key = DataKey::Escrow(token.clone());
+    env.storage().persistent().set(&key, &total);
+    env.storage()
+        .persistent()
+        .extend_ttl(&key, GAME_TTL_LEDGERS, GAME_TTL_LEDGERS);
+}
+
+/// Delete a game that never got going, refunding stakes. Nobody wins.
+fn cancel_game(env: &Env, key: &DataKey, game: GameState) {
+    let session_id = game.session_id;
+    remove_game(env, key);
+    env.storage()
+        .temporary()
+        .remove(&DataKey::PhaseHistory(session_id));
+    count_phase(env, &game.phase, -1);
+    unindex_active_game(env, session_id);
+    refund_stakes(env, &game);
+    notify_observer(env, &game);
+
+    GameCancelled {
+        session_id,
+        codemaker: game.codemaker,
+        codebreaker: game.codebreaker,
+    }
+    .publish(env);
+    log_event(env, EventKind::Cancelled, session_id);
+}
+
+/// Award the game to the codebreaker because the codemaker let the feedback
+/// timeout pass.
+fn time_out_codemaker(env: &Env, key: &DataKey, game: &mut GameState) {
+    game.current_guess = Vec::new(env);
+    let codebreaker = game.codebreaker.clone();
+    finish_game(env, game, Some(codebreaker.clone()), FinishReason::Timeout);
+    save_game(env, key, game);
+
+    GameTimedOut {
+        session_id: game.session_id,
+        loser: game.codemaker.clone(),
+        winner: codebreaker,
+    }
+    .publish(env);
+    log_event(env, EventKind::TimedOut, game.session_id);
+    notify_observer(env, game);
+}
+
+/// Return escrowed stakes to whoever has paid in: the codemaker always, the
+/// codebreaker once they have accepted. No-op for unstaked games.
+fn refund_stakes(env: &Env, game: &GameState) {
+    if let Some(token) = &game.stake_token {
+        release_escrow(env, token, &game.codemaker, game.codemaker_stake);
+        if game.phase != GamePhase::WaitingForAccept {
+            release_escrow(env, token, &game.codebreaker, game.codebreaker_stake);
+        }
+    }
+}
+
+/// Last ledger on which the current phase's timeout cannot yet be claimed,
+/// for the phases that have one. A sealed feedback doubles the feedback window.
+fn timeout_deadline(env: &Env, game: &GameState) -> Option<u32> {
+    let config = timeout_config(env);
+    let window = match game.phase {
+        GamePhase::WaitingForAccept => config.accept_window,
+        GamePhase::WaitingForCommitment => {
+            config.move_timeout.saturating_add(config.first_move_grace)
+        }
+        GamePhase::WaitingForFeedback if game.fair_play.sealed_feedback.is_some() => {
+            config.feedback_timeout.saturating_mul(2)
+        }
+        GamePhase::WaitingForFeedback => config.feedback_timeout,
+        GamePhase::WaitingForGuess if game.guess_count == 0 => config
+            .abandonment_window
+            .saturating_add(config.first_move_grace),
+        GamePhase::WaitingForGuess | GamePhase::Finished => return None,
+    };
+    // Admin-set windows are unbounded; a huge one means no deadline in reach
+    Some(game.phase_entered_ledger.saturating_add(window))
+}
+
+/// A codemaker's win is only final once the secret is revealed and the
+/// feedback can be disputed, when disputes are enabled at all.
+fn awaiting_reveal(env: &Env, game: &GameState) -> bool {
+    !game.revealed
+        && game.winner == Some(game.codemaker.clone())
+        && timeout_config(env).dispute_window > 0
+}
+
+fn dispute_window_open(env: &Env, game: &GameState) -> bool {
+    let window = timeout_config(env).dispute_window;
+    env.ledger().sequence() < game.phase_entered_ledger.saturating_add(window)
+}
+
+fn default_timeout_config() -> TimeoutConfig {
+    TimeoutConfig {
+        move_timeout: DEFAULT_MOVE_TIMEOUT,
+        feedback_timeout: DEFAULT_FEEDBACK_TIMEOUT,
+        abandonment_window: DEFAULT_ABANDONMENT_WINDOW,
+        accept_window: DEFAULT_ACCEPT_WINDOW,
+        dispute_window: 0,
+        first_move_grace: 0,
+    }
+}
+
+fn timeout_config(env: &Env) -> TimeoutConfig {
+    env.storage()
+        .instance()
+        .get(&DataKey::TimeoutConfig)
+        .expect("Timeout config not set")
 }
 
 #[cfg(test)]
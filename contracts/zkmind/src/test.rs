@@ -1,24 +1,233 @@
 #![cfg(test)]
 
-use soroban_sdk::{testutils::Address as _, Address, BytesN, Env, Vec};
+use soroban_sdk::{
+    contract, contracterror, contractimpl, symbol_short,
+    testutils::{Address as _, Events, Ledger},
+    token::{StellarAssetClient, TokenClient},
+    xdr::ToXdr,
+    Address, Bytes, BytesN, Env, IntoVal, String, Symbol, TryFromVal, Val, Vec,
+};
 
-use crate::{Error, GamePhase, ZKMindContract, ZKMindContractClient};
+use crate::{
+    ActionHint, Addresses, CommitScheme, ContractStatus, DataKey, DualGame, Error, EventKind,
+    EventRecord, Feedback, FinishReason, GameConfig, GamePhase, GameSpec, Hint, LegacyGameState,
+    PhaseCounts, PlayerStats, SolverState, TimeoutConfig, Tournament, ZKMindContract,
+    ZKMindContractClient, DEFAULT_MOVE_TIMEOUT, FEEDBACK_COMBINED, FEEDBACK_SPLIT,
+};
+
+/// Game Hub stand-in that records the last start/end calls it received.
+#[contract]
+pub struct MockGameHub;
+
+#[contractimpl]
+impl MockGameHub {
+    pub fn start_game(
+        env: Env,
+        _game_id: Address,
+        session_id: u32,
+        _player1: Address,
+        _player2: Address,
+        player1_points: i128,
+        player2_points: i128,
+    ) {
+        env.storage()
+            .instance()
+            .set(&symbol_short!("started"), &session_id);
+        env.storage()
+            .instance()
+            .set(&symbol_short!("points"), &(player1_points, player2_points));
+    }
+
+    pub fn end_game(env: Env, session_id: u32, player1_won: bool) {
+        env.storage()
+            .instance()
+            .set(&symbol_short!("ended"), &(session_id, player1_won));
+    }
+
+    pub fn last_started(env: Env) -> Option<u32> {
+        env.storage().instance().get(&symbol_short!("started"))
+    }
+
+    pub fn last_ended(env: Env) -> Option<(u32, bool)> {
+        env.storage().instance().get(&symbol_short!("ended"))
+    }
+
+    pub fn last_points(env: Env) -> Option<(i128, i128)> {
+        env.storage().instance().get(&symbol_short!("points"))
+    }
+}
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum HubError {
+    Closed = 1,
+}
+
+/// Game Hub stand-in whose `end_game` fails the way the test asks.
+#[contract]
+pub struct FailingGameHub;
+
+#[contractimpl]
+impl FailingGameHub {
+    pub fn start_game(
+        _env: Env,
+        _game_id: Address,
+        _session_id: u32,
+        _player1: Address,
+        _player2: Address,
+        _player1_points: i128,
+        _player2_points: i128,
+    ) {
+    }
+
+    pub fn end_game(env: Env, _session_id: u32, _player1_won: bool) -> Result<(), HubError> {
+        if env.storage().instance().has(&symbol_short!("reject")) {
+            return Err(HubError::Closed);
+        }
+        panic!("hub down");
+    }
+
+    pub fn set_rejecting(env: Env) {
+        env.storage()
+            .instance()
+            .set(&symbol_short!("reject"), &true);
+    }
+}
+
+/// Ratings stand-in with points set per player by the test.
+#[contract]
+pub struct MockRatings;
+
+#[contractimpl]
+impl MockRatings {
+    pub fn get_player_points(env: Env, player: Address) -> i128 {
+        env.storage().instance().get(&player).unwrap_or(0)
+    }
+
+    pub fn set_points(env: Env, player: Address, points: i128) {
+        env.storage().instance().set(&player, &points);
+    }
+}
+
+/// Observer stand-in that keeps every notification it receives.
+#[contract]
+pub struct MockObserver;
+
+#[contractimpl]
+impl MockObserver {
+    pub fn on_move(env: Env, session_id: u32, phase: GamePhase, guess_count: u32) {
+        let mut moves: Vec<(u32, GamePhase, u32)> = Self::moves(env.clone());
+        moves.push_back((session_id, phase, guess_count));
+        env.storage()
+            .instance()
+            .set(&symbol_short!("moves"), &moves);
+    }
+
+    pub fn moves(env: Env) -> Vec<(u32, GamePhase, u32)> {
+        env.storage()
+            .instance()
+            .get(&symbol_short!("moves"))
+            .unwrap_or(Vec::new(&env))
+    }
+}
+
+/// Observer stand-in that always fails.
+#[contract]
+pub struct PanickingObserver;
+
+#[contractimpl]
+impl PanickingObserver {
+    pub fn on_move(_env: Env, _session_id: u32, _phase: GamePhase, _guess_count: u32) {
+        panic!("observer down");
+    }
+}
+
+/// Verifier stand-in whose verdict is set by the test.
+#[contract]
+pub struct MockVerifier;
+
+#[contractimpl]
+impl MockVerifier {
+    pub fn verify_proof(env: Env, _public_inputs: Bytes, _proof_bytes: Bytes) -> bool {
+        env.storage()
+            .instance()
+            .get(&symbol_short!("valid"))
+            .unwrap_or(false)
+    }
+
+    pub fn verify_proof_with_vk(
+        env: Env,
+        vk: Bytes,
+        public_inputs: Bytes,
+        proof_bytes: Bytes,
+    ) -> bool {
+        env.storage().instance().set(&symbol_short!("vk"), &vk);
+        Self::verify_proof(env, public_inputs, proof_bytes)
+    }
+
+    pub fn last_vk(env: Env) -> Option<Bytes> {
+        env.storage().instance().get(&symbol_short!("vk"))
+    }
+
+    pub fn set_valid(env: Env, valid: bool) {
+        env.storage()
+            .instance()
+            .set(&symbol_short!("valid"), &valid);
+    }
+}
+
+fn test_config(env: &Env) -> GameConfig {
+    GameConfig {
+        code_length: 4,
+        num_colors: 6,
+        max_guesses: 12,
+        pipeline_depth: 1,
+        stake_token: None,
+        codemaker_stake: 0,
+        codebreaker_stake: 0,
+        draw_on_exhaustion: false,
+        commit_scheme: CommitScheme::Sha256,
+        trusted: false,
+        hints_allowed: false,
+        strict_proofs: false,
+        color_labels: Vec::new(env),
+        feedback_mode: FEEDBACK_SPLIT,
+        validity_proof: false,
+        sealed_feedback_allowed: false,
+        commit_deadline_ledgers: None,
+    }
+}
 
 fn setup_test() -> (Env, ZKMindContractClient<'static>, Address, Address) {
+    let (env, client, _hub, codemaker, codebreaker) = setup_test_with_namespace(0);
+    (env, client, codemaker, codebreaker)
+}
+
+fn setup_test_with_namespace(
+    namespace: u32,
+) -> (
+    Env,
+    ZKMindContractClient<'static>,
+    MockGameHubClient<'static>,
+    Address,
+    Address,
+) {
     let env = Env::default();
     env.mock_all_auths();
 
     let admin = Address::generate(&env);
-    let game_hub = Address::generate(&env);
+    let game_hub = env.register(MockGameHub, ());
     let verifier = Address::generate(&env);
 
-    let contract_id = env.register(ZKMindContract, (&admin, &game_hub, &verifier));
+    let contract_id = env.register(ZKMindContract, (&admin, &game_hub, &verifier, namespace));
     let client = ZKMindContractClient::new(&env, &contract_id);
+    let hub = MockGameHubClient::new(&env, &game_hub);
 
     let codemaker = Address::generate(&env);
     let codebreaker = Address::generate(&env);
 
-    (env, client, codemaker, codebreaker)
+    (env, client, hub, codemaker, codebreaker)
 }
 
 #[test]
@@ -26,6 +235,7 @@ fn test_new_game_and_commit() {
     let (env, client, codemaker, codebreaker) = setup_test();
 
     client.new_game(&1u32, &codemaker, &codebreaker);
+    client.accept_game(&1u32, &codebreaker);
 
     let game = client.get_game(&1u32);
     assert_eq!(game.phase, GamePhase::WaitingForCommitment);
@@ -46,6 +256,7 @@ fn test_full_game_codebreaker_wins() {
     let (env, client, codemaker, codebreaker) = setup_test();
 
     client.new_game(&1u32, &codemaker, &codebreaker);
+    client.accept_game(&1u32, &codebreaker);
 
     let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
     client.commit_code(&1u32, &codemaker, &commitment);
@@ -76,6 +287,7 @@ fn test_game_continues_after_partial_match() {
     let (env, client, codemaker, codebreaker) = setup_test();
 
     client.new_game(&1u32, &codemaker, &codebreaker);
+    client.accept_game(&1u32, &codebreaker);
 
     let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
     client.commit_code(&1u32, &codemaker, &commitment);
@@ -97,3 +309,4360 @@ fn test_game_continues_after_partial_match() {
     assert_eq!(game.guess_count, 1);
     assert_eq!(game.feedbacks.len(), 1);
 }
+
+#[test]
+fn test_accept_handshake() {
+    let (_env, client, codemaker, codebreaker) = setup_test();
+
+    client.new_game(&1u32, &codemaker, &codebreaker);
+
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::WaitingForAccept);
+
+    // Only the invited codebreaker can accept
+    let result = client.try_accept_game(&1u32, &codemaker);
+    assert_eq!(result, Err(Ok(Error::NotCodeBreaker)));
+
+    client.accept_game(&1u32, &codebreaker);
+
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::WaitingForCommitment);
+
+    // Accepting twice is rejected
+    let result = client.try_accept_game(&1u32, &codebreaker);
+    assert_eq!(result, Err(Ok(Error::InvalidPhase)));
+}
+
+#[test]
+fn test_commit_before_accept_rejected() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    client.new_game(&1u32, &codemaker, &codebreaker);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let result = client.try_commit_code(&1u32, &codemaker, &commitment);
+    assert_eq!(result, Err(Ok(Error::InvalidPhase)));
+}
+
+#[test]
+fn test_timeout_config_defaults_and_update() {
+    let (_env, client, _codemaker, _codebreaker) = setup_test();
+
+    let config = client.get_timeout_config();
+    assert_eq!(config.move_timeout, 17_280);
+    assert_eq!(config.feedback_timeout, 17_280);
+    assert_eq!(config.abandonment_window, 120_960);
+    assert_eq!(config.accept_window, 17_280);
+    assert_eq!(config.dispute_window, 0);
+    assert_eq!(config.first_move_grace, 0);
+
+    let updated = TimeoutConfig {
+        move_timeout: 100,
+        feedback_timeout: 200,
+        abandonment_window: 300,
+        accept_window: 400,
+        dispute_window: 500,
+        first_move_grace: 600,
+    };
+    client.set_timeout_config(&updated);
+    assert_eq!(client.get_timeout_config(), updated);
+
+    // Zero-length windows would make every move instantly claimable
+    let result = client.try_set_timeout_config(&TimeoutConfig {
+        move_timeout: 0,
+        feedback_timeout: 200,
+        abandonment_window: 300,
+        accept_window: 400,
+        dispute_window: 500,
+        first_move_grace: 600,
+    });
+    assert_eq!(result, Err(Ok(Error::InvalidConfig)));
+}
+
+#[test]
+fn test_new_game_and_commit_in_one_call() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+
+    // Both players authorized creation, so no accept step is needed
+    assert_eq!(env.auths().len(), 2);
+
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::WaitingForGuess);
+    assert_eq!(game.commitment, commitment);
+}
+
+#[test]
+fn test_solved_event_carries_winner_and_loser() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_feedback(&1u32, &codemaker, &4u32, &0u32, &proof_hash);
+
+    let (_, topics, data) = env.events().all().last().unwrap();
+    let expected_topics: Vec<Val> =
+        (symbol_short!("game"), symbol_short!("solved"), 1u32).into_val(&env);
+    assert_eq!(topics, expected_topics);
+
+    let (winner, loser) = <(Address, Address)>::try_from_val(&env, &data).unwrap();
+    assert_eq!(winner, codebreaker);
+    assert_eq!(loser, codemaker);
+}
+
+#[test]
+fn test_guess_must_be_canonical() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+
+    // A color equal to num_colors is out of range
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(6u32);
+    let result = client.try_submit_guess(&1u32, &codebreaker, &guess);
+    assert_eq!(result, Err(Ok(Error::InvalidGuessValue)));
+
+    // Trailing padding is rejected
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    guess.push_back(0u32);
+    let result = client.try_submit_guess(&1u32, &codebreaker, &guess);
+    assert_eq!(result, Err(Ok(Error::InvalidGuessValue)));
+
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::WaitingForGuess);
+}
+
+#[test]
+fn test_export_transcript_for_finished_game() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+
+    env.ledger().set_sequence_number(150);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_feedback(&1u32, &codemaker, &4u32, &0u32, &proof_hash);
+
+    let transcript = client.export_transcript(&1u32);
+    assert_eq!(transcript.session_id, 1);
+    assert_eq!(transcript.codemaker, codemaker);
+    assert_eq!(transcript.codebreaker, codebreaker);
+    assert_eq!(transcript.code_length, 4);
+    assert_eq!(transcript.num_colors, 6);
+    assert_eq!(transcript.max_guesses, 12);
+    assert_eq!(transcript.commitment, commitment);
+    assert_eq!(transcript.guesses.len(), 1);
+    assert_eq!(transcript.guesses.get(0).unwrap(), guess);
+    assert_eq!(transcript.feedbacks.len(), 1);
+    assert_eq!(transcript.feedbacks.get(0).unwrap().correct_position, 4);
+    assert_eq!(transcript.winner, Some(codebreaker));
+    assert_eq!(transcript.created_ledger, 100);
+    assert_eq!(transcript.finished_ledger, Some(150));
+}
+
+#[test]
+fn test_give_up_awards_codemaker() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &1u32, &proof_hash);
+
+    // Only the codebreaker can concede
+    let result = client.try_give_up(&1u32, &codemaker);
+    assert_eq!(result, Err(Ok(Error::NotCodeBreaker)));
+
+    client.give_up(&1u32, &codebreaker);
+
+    let (_, topics, data) = env.events().all().last().unwrap();
+    let expected_topics: Vec<Val> =
+        (symbol_short!("game"), symbol_short!("gave_up"), 1u32).into_val(&env);
+    assert_eq!(topics, expected_topics);
+    let (winner, loser) = <(Address, Address)>::try_from_val(&env, &data).unwrap();
+    assert_eq!(winner, codemaker);
+    assert_eq!(loser, codebreaker);
+
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::Finished);
+    assert_eq!(game.winner, Some(codemaker));
+    assert_eq!(game.feedbacks.len(), 1);
+
+    let result = client.try_give_up(&1u32, &codebreaker);
+    assert_eq!(result, Err(Ok(Error::GameAlreadyEnded)));
+}
+
+#[test]
+fn test_feedback_bounds_follow_code_length() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let config = GameConfig {
+        code_length: 5,
+        max_guesses: 10,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    assert_eq!(client.get_config(&1u32), config);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    guess.push_back(4u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+
+    // Six pegs cannot be scored on a five-peg code
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    let result = client.try_submit_feedback(&1u32, &codemaker, &4u32, &2u32, &proof_hash);
+    assert_eq!(result, Err(Ok(Error::InvalidFeedback)));
+
+    // Five red pegs solve a five-peg code
+    client.submit_feedback(&1u32, &codemaker, &5u32, &0u32, &proof_hash);
+
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::Finished);
+    assert_eq!(game.winner, Some(codebreaker));
+}
+
+#[test]
+fn test_new_game_rejects_invalid_config() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let config = GameConfig {
+        code_length: 0,
+        ..test_config(&env)
+    };
+    let result = client.try_new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    assert_eq!(result, Err(Ok(Error::InvalidConfig)));
+}
+
+#[test]
+fn test_active_game_between() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let stranger = Address::generate(&env);
+
+    assert_eq!(client.active_game_between(&codemaker, &codebreaker), None);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&7u32, &codemaker, &codebreaker, &commitment);
+
+    // Found regardless of argument order, but not for other pairs
+    assert_eq!(
+        client.active_game_between(&codemaker, &codebreaker),
+        Some(7)
+    );
+    assert_eq!(
+        client.active_game_between(&codebreaker, &codemaker),
+        Some(7)
+    );
+    assert_eq!(client.active_game_between(&codemaker, &stranger), None);
+
+    client.give_up(&7u32, &codebreaker);
+    assert_eq!(client.active_game_between(&codemaker, &codebreaker), None);
+}
+
+#[test]
+fn test_randomness_anchor_differs_across_ledgers() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    env.ledger().set_sequence_number(100);
+    client.new_game(&1u32, &codemaker, &codebreaker);
+    let first = client.get_game(&1u32).randomness_anchor;
+
+    env.ledger().set_sequence_number(101);
+    client.new_game(&2u32, &codemaker, &codebreaker);
+    let second = client.get_game(&2u32).randomness_anchor;
+
+    assert_ne!(first, second);
+    assert_ne!(first, BytesN::from_array(&env, &[0u8; 32]));
+    // The anchor is fixed at creation
+    assert_eq!(client.get_game(&1u32).randomness_anchor, first);
+}
+
+#[test]
+fn test_entrypoints_enforce_required_phase() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+
+    // WaitingForAccept: nothing but accept_game
+    client.new_game(&1u32, &codemaker, &codebreaker);
+    assert_eq!(
+        client.try_commit_code(&1u32, &codemaker, &commitment),
+        Err(Ok(Error::InvalidPhase))
+    );
+    assert_eq!(
+        client.try_submit_guess(&1u32, &codebreaker, &guess),
+        Err(Ok(Error::InvalidPhase))
+    );
+    assert_eq!(
+        client.try_give_up(&1u32, &codebreaker),
+        Err(Ok(Error::InvalidPhase))
+    );
+    assert_eq!(
+        client.try_report_result(&1u32),
+        Err(Ok(Error::InvalidPhase))
+    );
+
+    // WaitingForCommitment
+    client.accept_game(&1u32, &codebreaker);
+    assert_eq!(
+        client.try_accept_game(&1u32, &codebreaker),
+        Err(Ok(Error::InvalidPhase))
+    );
+    assert_eq!(
+        client.try_submit_guess(&1u32, &codebreaker, &guess),
+        Err(Ok(Error::InvalidPhase))
+    );
+
+    // WaitingForGuess
+    client.commit_code(&1u32, &codemaker, &commitment);
+    assert_eq!(
+        client.try_commit_code(&1u32, &codemaker, &commitment),
+        Err(Ok(Error::InvalidPhase))
+    );
+    assert_eq!(
+        client.try_submit_feedback(&1u32, &codemaker, &1u32, &1u32, &proof_hash),
+        Err(Ok(Error::InvalidPhase))
+    );
+
+    // WaitingForFeedback
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    assert_eq!(
+        client.try_submit_guess(&1u32, &codebreaker, &guess),
+        Err(Ok(Error::InvalidPhase))
+    );
+    assert_eq!(
+        client.try_report_result(&1u32),
+        Err(Ok(Error::InvalidPhase))
+    );
+}
+
+#[test]
+fn test_pipelined_guesses_answered_in_order() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let config = GameConfig {
+        pipeline_depth: 2,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+
+    let mut first = Vec::new(&env);
+    first.push_back(0u32);
+    first.push_back(0u32);
+    first.push_back(1u32);
+    first.push_back(1u32);
+    let mut second = Vec::new(&env);
+    second.push_back(2u32);
+    second.push_back(2u32);
+    second.push_back(3u32);
+    second.push_back(3u32);
+
+    // Two guesses queue before any feedback; a third exceeds the depth
+    client.submit_guess(&1u32, &codebreaker, &first);
+    client.submit_guess(&1u32, &codebreaker, &second);
+    assert_eq!(
+        client.try_submit_guess(&1u32, &codebreaker, &first),
+        Err(Ok(Error::InvalidPhase))
+    );
+
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::WaitingForFeedback);
+    assert_eq!(game.pending_guesses.len(), 2);
+    assert_eq!(game.current_guess, first);
+
+    // Feedback answers the oldest guess first
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &0u32, &proof_hash);
+
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::WaitingForFeedback);
+    assert_eq!(game.guesses.get(0).unwrap(), first);
+    assert_eq!(game.current_guess, second);
+
+    client.submit_feedback(&1u32, &codemaker, &0u32, &2u32, &proof_hash);
+
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::WaitingForGuess);
+    assert_eq!(game.guesses.get(1).unwrap(), second);
+    assert_eq!(game.guess_count, 2);
+    assert!(game.pending_guesses.is_empty());
+}
+
+#[test]
+fn test_inferred_colors_lower_bounds() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    // Secret is [1, 0, 0, 4]
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+
+    // [0, 0, 0, 1] scores 2 red + 1 white: at least two 0s
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(0u32);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &2u32, &1u32, &proof_hash);
+
+    let bounds = client.inferred_colors(&1u32);
+    assert_eq!(bounds.len(), 6);
+    assert_eq!(bounds.get(0).unwrap(), 2);
+    assert_eq!(bounds.get(1).unwrap(), 0);
+
+    // [1, 1, 1, 4] scores 2 red: at least one 1
+    let mut guess = Vec::new(&env);
+    guess.push_back(1u32);
+    guess.push_back(1u32);
+    guess.push_back(1u32);
+    guess.push_back(4u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &2u32, &0u32, &proof_hash);
+
+    let bounds = client.inferred_colors(&1u32);
+    let mut expected = Vec::new(&env);
+    for count in [2u32, 1, 0, 0, 0, 0] {
+        expected.push_back(count);
+    }
+    assert_eq!(bounds, expected);
+}
+
+#[test]
+fn test_namespaced_session_id_reaches_hub() {
+    let (env, client, hub, codemaker, codebreaker) = setup_test_with_namespace(3);
+
+    client.new_game(&5u32, &codemaker, &codebreaker);
+    assert_eq!(hub.last_started(), None);
+
+    client.accept_game(&5u32, &codebreaker);
+    assert_eq!(hub.last_started(), Some((3 << 24) | 5));
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&5u32, &codemaker, &commitment);
+    client.give_up(&5u32, &codebreaker);
+    client.report_result(&5u32);
+    assert_eq!(hub.last_ended(), Some(((3 << 24) | 5, true)));
+
+    // Ids that would spill into the namespace byte are rejected
+    let result = client.try_new_game(&0x0100_0000u32, &codemaker, &codebreaker);
+    assert_eq!(result, Err(Ok(Error::InvalidSessionId)));
+}
+
+#[test]
+fn test_next_action_for_each_phase() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let stranger = Address::generate(&env);
+
+    client.new_game(&1u32, &codemaker, &codebreaker);
+    assert_eq!(client.next_action(&1u32, &codemaker), ActionHint::Wait);
+    assert_eq!(client.next_action(&1u32, &codebreaker), ActionHint::Accept);
+    assert_eq!(
+        client.try_next_action(&1u32, &stranger),
+        Err(Ok(Error::NotPlayer))
+    );
+
+    client.accept_game(&1u32, &codebreaker);
+    assert_eq!(client.next_action(&1u32, &codemaker), ActionHint::Commit);
+    assert_eq!(client.next_action(&1u32, &codebreaker), ActionHint::Wait);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    assert_eq!(client.next_action(&1u32, &codemaker), ActionHint::Wait);
+    assert_eq!(client.next_action(&1u32, &codebreaker), ActionHint::Guess);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    assert_eq!(
+        client.next_action(&1u32, &codemaker),
+        ActionHint::GiveFeedback
+    );
+    assert_eq!(client.next_action(&1u32, &codebreaker), ActionHint::Wait);
+
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_feedback(&1u32, &codemaker, &4u32, &0u32, &proof_hash);
+    assert_eq!(client.next_action(&1u32, &codemaker), ActionHint::GameOver);
+    assert_eq!(
+        client.next_action(&1u32, &codebreaker),
+        ActionHint::GameOver
+    );
+}
+
+/// Deploy a test token and fund both players with `amount` each.
+fn setup_stake_token(
+    env: &Env,
+    codemaker: &Address,
+    codebreaker: &Address,
+    amount: i128,
+) -> TokenClient<'static> {
+    let issuer = Address::generate(env);
+    let token_id = env.register_stellar_asset_contract_v2(issuer).address();
+    let asset = StellarAssetClient::new(env, &token_id);
+    asset.mint(codemaker, &amount);
+    asset.mint(codebreaker, &amount);
+    TokenClient::new(env, &token_id)
+}
+
+#[test]
+fn test_draw_refunds_both_stakes() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+
+    let config = GameConfig {
+        max_guesses: 1,
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 400,
+        codebreaker_stake: 400,
+        draw_on_exhaustion: true,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    assert_eq!(token.balance(&client.address), 800);
+    assert_eq!(token.balance(&codemaker), 600);
+    assert_eq!(token.balance(&codebreaker), 600);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &1u32, &proof_hash);
+
+    // Exhausting the only guess is a draw under this rule
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::Finished);
+    assert_eq!(game.winner, None);
+    assert_eq!(client.try_report_result(&1u32), Err(Ok(Error::NoWinner)));
+
+    assert_eq!(client.withdraw_winnings(&1u32, &codebreaker), 400);
+    assert_eq!(token.balance(&codemaker), 1_000);
+    assert_eq!(token.balance(&codebreaker), 1_000);
+    assert_eq!(token.balance(&client.address), 0);
+
+    // The settled flag blocks a second refund
+    assert_eq!(
+        client.try_withdraw_winnings(&1u32, &codemaker),
+        Err(Ok(Error::AlreadySettled))
+    );
+}
+
+#[test]
+fn test_winner_collects_both_stakes() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+
+    let config = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 250,
+        codebreaker_stake: 250,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    client.give_up(&1u32, &codebreaker);
+
+    assert_eq!(
+        client.try_withdraw_winnings(&1u32, &codebreaker),
+        Err(Ok(Error::NotWinner))
+    );
+    assert_eq!(client.withdraw_winnings(&1u32, &codemaker), 500);
+    assert_eq!(token.balance(&codemaker), 1_250);
+    assert_eq!(token.balance(&codebreaker), 750);
+}
+
+#[test]
+fn test_result_hash_is_deterministic() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+
+    // Only finished games have a result
+    assert_eq!(client.try_result_hash(&1u32), Err(Ok(Error::InvalidPhase)));
+
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_feedback(&1u32, &codemaker, &4u32, &0u32, &proof_hash);
+
+    let hash = client.result_hash(&1u32);
+    assert_eq!(client.result_hash(&1u32), hash);
+
+    let final_feedback = Feedback {
+        correct_position: 4,
+        correct_color: 0,
+        proof_hash,
+    };
+    let outcome = (
+        1u32,
+        Some(codebreaker),
+        1u32,
+        commitment,
+        Some(final_feedback),
+    );
+    let expected: BytesN<32> = env.crypto().sha256(&outcome.to_xdr(&env)).into();
+    assert_eq!(hash, expected);
+}
+
+#[test]
+fn test_commit_timeout_cancels_and_refunds() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+    env.ledger().set_sequence_number(100);
+
+    let config = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 300,
+        codebreaker_stake: 300,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    assert_eq!(token.balance(&client.address), 600);
+
+    // The deadline is move_timeout ledgers after the commit phase began
+    let move_timeout = client.get_timeout_config().move_timeout;
+    env.ledger().set_sequence_number(100 + move_timeout);
+    assert_eq!(
+        client.try_claim_commit_timeout(&1u32, &codebreaker),
+        Err(Ok(Error::TimeoutNotReached))
+    );
+
+    env.ledger().set_sequence_number(100 + move_timeout + 1);
+    assert_eq!(
+        client.try_claim_commit_timeout(&1u32, &codemaker),
+        Err(Ok(Error::NotCodeBreaker))
+    );
+    client.claim_commit_timeout(&1u32, &codebreaker);
+
+    assert_eq!(token.balance(&codemaker), 1_000);
+    assert_eq!(token.balance(&codebreaker), 1_000);
+    assert_eq!(token.balance(&client.address), 0);
+    assert_eq!(client.try_get_game(&1u32), Err(Ok(Error::GameNotFound)));
+}
+
+#[test]
+fn test_accept_timeout_cancels_and_refunds_codemaker() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+    env.ledger().set_sequence_number(100);
+
+    let config = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 300,
+        codebreaker_stake: 300,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    assert_eq!(token.balance(&client.address), 300);
+
+    let accept_window = client.get_timeout_config().accept_window;
+    env.ledger().set_sequence_number(100 + accept_window);
+    assert_eq!(
+        client.try_claim_accept_timeout(&1u32, &codemaker),
+        Err(Ok(Error::TimeoutNotReached))
+    );
+
+    env.ledger().set_sequence_number(100 + accept_window + 1);
+    assert_eq!(
+        client.try_claim_accept_timeout(&1u32, &codebreaker),
+        Err(Ok(Error::NotCodeMaker))
+    );
+    client.claim_accept_timeout(&1u32, &codemaker);
+
+    assert_eq!(token.balance(&codemaker), 1_000);
+    assert_eq!(token.balance(&codebreaker), 1_000);
+    assert_eq!(token.balance(&client.address), 0);
+    assert_eq!(client.try_get_game(&1u32), Err(Ok(Error::GameNotFound)));
+}
+
+/// Commitment as `reveal_code` recomputes it: sha256 of each peg as a
+/// big-endian u32, followed by the salt.
+fn sha256_commitment(env: &Env, secret: &Vec<u32>, salt: &BytesN<32>) -> BytesN<32> {
+    let mut preimage = Bytes::new(env);
+    for peg in secret.iter() {
+        preimage.extend_from_array(&peg.to_be_bytes());
+    }
+    preimage.extend_from_array(&salt.to_array());
+    env.crypto().sha256(&preimage).into()
+}
+
+#[test]
+fn test_reveal_rejects_malformed_secret_before_comparing() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let salt = BytesN::from_array(&env, &[0x11u8; 32]);
+
+    // The codemaker committed to an out-of-range color
+    let mut malformed = Vec::new(&env);
+    malformed.push_back(0u32);
+    malformed.push_back(1u32);
+    malformed.push_back(2u32);
+    malformed.push_back(9u32);
+    let commitment = sha256_commitment(&env, &malformed, &salt);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    client.give_up(&1u32, &codebreaker);
+
+    // The hash matches, but the secret is still refused
+    assert_eq!(
+        client.try_reveal_code(&1u32, &codemaker, &malformed, &salt),
+        Err(Ok(Error::InvalidReveal))
+    );
+
+    // Wrong length is refused the same way
+    let mut short = Vec::new(&env);
+    short.push_back(0u32);
+    assert_eq!(
+        client.try_reveal_code(&1u32, &codemaker, &short, &salt),
+        Err(Ok(Error::InvalidReveal))
+    );
+    assert!(!client.get_game(&1u32).revealed);
+}
+
+#[test]
+fn test_reveal_checks_commitment() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let salt = BytesN::from_array(&env, &[0x11u8; 32]);
+
+    let mut secret = Vec::new(&env);
+    secret.push_back(3u32);
+    secret.push_back(1u32);
+    secret.push_back(4u32);
+    secret.push_back(1u32);
+    let commitment = sha256_commitment(&env, &secret, &salt);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+
+    // Not before the game is over
+    assert_eq!(
+        client.try_reveal_code(&1u32, &codemaker, &secret, &salt),
+        Err(Ok(Error::InvalidPhase))
+    );
+    client.give_up(&1u32, &codebreaker);
+
+    let mut other = secret.clone();
+    other.set(0, 5u32);
+    assert_eq!(
+        client.try_reveal_code(&1u32, &codemaker, &other, &salt),
+        Err(Ok(Error::CommitmentMismatch))
+    );
+    assert_eq!(
+        client.try_get_revealed_secret(&1u32),
+        Err(Ok(Error::InvalidPhase))
+    );
+
+    client.reveal_code(&1u32, &codemaker, &secret, &salt);
+    assert!(client.get_game(&1u32).revealed);
+    assert_eq!(client.get_revealed_secret(&1u32), secret);
+    assert_eq!(
+        client.try_reveal_code(&1u32, &codemaker, &secret, &salt),
+        Err(Ok(Error::AlreadyRevealed))
+    );
+}
+
+#[test]
+fn test_reveal_with_each_commit_scheme() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let salt = BytesN::from_array(&env, &[0x22u8; 32]);
+
+    let mut secret = Vec::new(&env);
+    secret.push_back(2u32);
+    secret.push_back(0u32);
+    secret.push_back(5u32);
+    secret.push_back(5u32);
+    let mut preimage = Bytes::new(&env);
+    for peg in secret.iter() {
+        preimage.extend_from_array(&peg.to_be_bytes());
+    }
+    preimage.extend_from_array(&salt.to_array());
+
+    let schemes = [
+        (
+            CommitScheme::Sha256,
+            BytesN::from(env.crypto().sha256(&preimage)),
+        ),
+        (
+            CommitScheme::Keccak256,
+            BytesN::from(env.crypto().keccak256(&preimage)),
+        ),
+    ];
+    for (session_id, (scheme, commitment)) in (1u32..).zip(schemes) {
+        let config = GameConfig {
+            commit_scheme: scheme,
+            ..test_config(&env)
+        };
+        client.new_game_with_config(&session_id, &codemaker, &codebreaker, &config);
+        client.accept_game(&session_id, &codebreaker);
+        client.commit_code(&session_id, &codemaker, &commitment);
+        client.give_up(&session_id, &codebreaker);
+
+        client.reveal_code(&session_id, &codemaker, &secret, &salt);
+        assert!(client.get_game(&session_id).revealed);
+    }
+}
+
+#[test]
+fn test_total_escrow_tracks_deposits_and_payouts() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+    assert_eq!(client.total_escrow(&token.address), 0);
+
+    let config = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 100,
+        codebreaker_stake: 100,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    assert_eq!(client.total_escrow(&token.address), 100);
+    client.accept_game(&1u32, &codebreaker);
+    assert_eq!(client.total_escrow(&token.address), 200);
+
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&2u32, &codebreaker);
+    assert_eq!(client.total_escrow(&token.address), 400);
+    assert_eq!(token.balance(&client.address), 400);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    client.give_up(&1u32, &codebreaker);
+    client.withdraw_winnings(&1u32, &codemaker);
+    assert_eq!(client.total_escrow(&token.address), 200);
+    assert_eq!(token.balance(&client.address), 200);
+}
+
+#[test]
+fn test_migrate_backfills_legacy_game() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(500);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let legacy = LegacyGameState {
+        session_id: 9,
+        codemaker: codemaker.clone(),
+        codebreaker: codebreaker.clone(),
+        phase: GamePhase::WaitingForFeedback,
+        commitment: BytesN::from_array(&env, &[0xABu8; 32]),
+        guesses: Vec::new(&env),
+        feedbacks: Vec::new(&env),
+        guess_count: 0,
+        max_guesses: 12,
+        winner: None,
+        current_guess: guess.clone(),
+    };
+    env.as_contract(&client.address, || {
+        env.storage().temporary().set(&DataKey::Game(9), &legacy);
+        // The original deployment never set these
+        env.storage().instance().remove(&DataKey::TimeoutConfig);
+        env.storage().instance().remove(&DataKey::GameNamespace);
+    });
+
+    let mut ids = Vec::new(&env);
+    ids.push_back(9u32);
+    ids.push_back(10u32); // unknown ids are skipped
+    assert_eq!(client.migrate(&ids), 1);
+    assert_eq!(
+        client.get_timeout_config().move_timeout,
+        DEFAULT_MOVE_TIMEOUT
+    );
+
+    let game = client.get_game(&9u32);
+    assert_eq!(game.phase, GamePhase::WaitingForFeedback);
+    assert_eq!(game.code_length, 4);
+    assert_eq!(game.num_colors, 6);
+    assert_eq!(game.created_ledger, 500);
+    assert_eq!(game.current_guess, guess);
+    assert_eq!(game.pending_guesses.len(), 1);
+    assert_eq!(
+        client.active_game_between(&codemaker, &codebreaker),
+        Some(9)
+    );
+
+    // The migrated game plays on normally
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_feedback(&9u32, &codemaker, &4u32, &0u32, &proof_hash);
+    assert_eq!(client.get_game(&9u32).winner, Some(codebreaker));
+
+    assert_eq!(client.try_migrate(&ids), Err(Ok(Error::AlreadyMigrated)));
+}
+
+#[test]
+fn test_finish_reason_records_exhaustion() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let config = GameConfig {
+        max_guesses: 2,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &0u32, &proof_hash);
+    assert_eq!(
+        client.try_get_finish_reason(&1u32),
+        Err(Ok(Error::InvalidPhase))
+    );
+
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &0u32, &proof_hash);
+
+    assert_eq!(client.get_finish_reason(&1u32), FinishReason::Exhausted);
+    assert_eq!(client.get_game(&1u32).winner, Some(codemaker));
+}
+
+#[test]
+fn test_zero_proof_hash_requires_trusted_game() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let trusted = GameConfig {
+        trusted: true,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &test_config(&env));
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &trusted);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    for session_id in [1u32, 2u32] {
+        client.accept_game(&session_id, &codebreaker);
+        client.commit_code(&session_id, &codemaker, &commitment);
+        client.submit_guess(&session_id, &codebreaker, &guess);
+    }
+
+    let zero_hash = BytesN::from_array(&env, &[0u8; 32]);
+    assert_eq!(
+        client.try_submit_feedback(&1u32, &codemaker, &1u32, &0u32, &zero_hash),
+        Err(Ok(Error::MissingProof))
+    );
+    client.submit_feedback(&2u32, &codemaker, &1u32, &0u32, &zero_hash);
+
+    assert_eq!(client.get_game(&2u32).feedbacks.len(), 1);
+    assert!(client.get_config(&2u32).trusted);
+}
+
+#[test]
+fn test_four_player_tournament() {
+    let (env, client, _codemaker, _codebreaker) = setup_test();
+
+    let mut players = Vec::new(&env);
+    for _ in 0..4 {
+        players.push_back(Address::generate(&env));
+    }
+    let tournament_id = client.new_tournament(&players);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+
+    // Round one: the codemaker wins the first match, the codebreaker the second
+    let round_one = client.get_tournament(&tournament_id).matches;
+    assert_eq!(round_one.len(), 2);
+    for session_id in round_one.iter() {
+        let game = client.get_game(&session_id);
+        client.accept_game(&session_id, &game.codebreaker);
+        client.commit_code(&session_id, &game.codemaker, &commitment);
+    }
+    assert_eq!(
+        client.try_advance_tournament(&tournament_id),
+        Err(Ok(Error::InvalidPhase))
+    );
+
+    let first = round_one.get_unchecked(0);
+    client.give_up(&first, &players.get_unchecked(1));
+    let second = round_one.get_unchecked(1);
+    client.submit_guess(&second, &players.get_unchecked(3), &guess);
+    client.submit_feedback(
+        &second,
+        &players.get_unchecked(2),
+        &4u32,
+        &0u32,
+        &proof_hash,
+    );
+
+    client.advance_tournament(&tournament_id);
+    let tournament = client.get_tournament(&tournament_id);
+    assert_eq!(tournament.round, 2);
+    assert_eq!(tournament.matches.len(), 1);
+
+    // Final: the round-one winners meet, and the codebreaker cracks the code
+    let final_id = tournament.matches.get_unchecked(0);
+    let game = client.get_game(&final_id);
+    assert_eq!(game.codemaker, players.get_unchecked(0));
+    assert_eq!(game.codebreaker, players.get_unchecked(3));
+    client.accept_game(&final_id, &game.codebreaker);
+    client.commit_code(&final_id, &game.codemaker, &commitment);
+    client.submit_guess(&final_id, &game.codebreaker, &guess);
+    client.submit_feedback(&final_id, &game.codemaker, &4u32, &0u32, &proof_hash);
+
+    client.advance_tournament(&tournament_id);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    let expected_topics: Vec<Val> = (
+        Symbol::new(&env, "tournament"),
+        symbol_short!("won"),
+        tournament_id,
+    )
+        .into_val(&env);
+    assert_eq!(topics, expected_topics);
+    assert_eq!(
+        Address::try_from_val(&env, &data).unwrap(),
+        players.get_unchecked(3)
+    );
+    assert_eq!(
+        client.get_tournament(&tournament_id),
+        Tournament {
+            players: players.clone(),
+            round: 2,
+            matches: tournament.matches,
+            entrants: tournament.entrants,
+            walkovers: tournament.walkovers,
+            champion: Some(players.get_unchecked(3)),
+        }
+    );
+    assert_eq!(
+        client.try_advance_tournament(&tournament_id),
+        Err(Ok(Error::GameAlreadyEnded))
+    );
+}
+
+#[test]
+fn test_unequal_stakes() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+
+    let config = GameConfig {
+        max_guesses: 1,
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 500,
+        codebreaker_stake: 200,
+        ..test_config(&env)
+    };
+    let draw_config = GameConfig {
+        draw_on_exhaustion: true,
+        ..config.clone()
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &draw_config);
+    client.accept_game(&1u32, &codebreaker);
+    client.accept_game(&2u32, &codebreaker);
+    assert_eq!(token.balance(&codemaker), 0);
+    assert_eq!(token.balance(&codebreaker), 600);
+    assert_eq!(client.total_escrow(&token.address), 1_400);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+
+    // The winner takes the combined pot
+    client.commit_code(&1u32, &codemaker, &commitment);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &4u32, &0u32, &proof_hash);
+    assert_eq!(client.withdraw_winnings(&1u32, &codebreaker), 700);
+    assert_eq!(token.balance(&codebreaker), 1_300);
+
+    // A draw hands each player back exactly what they put in
+    client.commit_code(&2u32, &codemaker, &commitment);
+    client.submit_guess(&2u32, &codebreaker, &guess);
+    client.submit_feedback(&2u32, &codemaker, &1u32, &0u32, &proof_hash);
+    assert_eq!(client.withdraw_winnings(&2u32, &codemaker), 500);
+    assert_eq!(token.balance(&codemaker), 500);
+    assert_eq!(token.balance(&codebreaker), 1_500);
+    assert_eq!(client.total_escrow(&token.address), 0);
+}
+
+#[test]
+fn test_get_last_feedback() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    assert_eq!(
+        client.try_get_last_feedback(&1u32),
+        Err(Ok(Error::NoFeedbackYet))
+    );
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &0u32, &proof_hash);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &2u32, &1u32, &proof_hash);
+
+    assert_eq!(
+        client.get_last_feedback(&1u32),
+        Feedback {
+            correct_position: 2,
+            correct_color: 1,
+            proof_hash,
+        }
+    );
+}
+
+#[test]
+fn test_events_replay_guesses_and_feedback() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let config = GameConfig {
+        pipeline_depth: 2,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+
+    let guess_topics: Vec<Val> =
+        (symbol_short!("game"), symbol_short!("guess"), 1u32).into_val(&env);
+    let feedback_topics: Vec<Val> =
+        (symbol_short!("game"), symbol_short!("feedback"), 1u32).into_val(&env);
+    let mut guesses: Vec<Vec<u32>> = Vec::new(&env);
+    let mut feedbacks: Vec<Feedback> = Vec::new(&env);
+    let mut phase = GamePhase::WaitingForGuess;
+    let mut replay = |env: &Env| {
+        for (_, topics, data) in env.events().all().iter() {
+            if topics == guess_topics {
+                guesses.push_back(Vec::try_from_val(env, &data).unwrap());
+            } else if topics == feedback_topics {
+                let (correct_position, correct_color, proof_hash, next) =
+                    <(u32, u32, BytesN<32>, GamePhase)>::try_from_val(env, &data).unwrap();
+                feedbacks.push_back(Feedback {
+                    correct_position,
+                    correct_color,
+                    proof_hash,
+                });
+                phase = next;
+            }
+        }
+    };
+
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    let mut first = Vec::new(&env);
+    first.push_back(0u32);
+    first.push_back(1u32);
+    first.push_back(2u32);
+    first.push_back(3u32);
+    let mut second = Vec::new(&env);
+    second.push_back(3u32);
+    second.push_back(2u32);
+    second.push_back(1u32);
+    second.push_back(0u32);
+
+    // Two pipelined guesses, answered oldest first, then the winning guess
+    client.submit_guess(&1u32, &codebreaker, &first);
+    replay(&env);
+    client.submit_guess(&1u32, &codebreaker, &second);
+    replay(&env);
+    client.submit_feedback(&1u32, &codemaker, &0u32, &4u32, &proof_hash);
+    replay(&env);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &2u32, &proof_hash);
+    replay(&env);
+    client.submit_guess(&1u32, &codebreaker, &first);
+    replay(&env);
+    client.submit_feedback(&1u32, &codemaker, &4u32, &0u32, &proof_hash);
+    replay(&env);
+
+    let game = client.get_game(&1u32);
+    assert_eq!(guesses, game.guesses);
+    assert_eq!(feedbacks, game.feedbacks);
+    assert_eq!(phase, game.phase);
+}
+
+#[test]
+fn test_hint_costs_a_guess() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let config = GameConfig {
+        max_guesses: 2,
+        hints_allowed: true,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &test_config(&env));
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    for session_id in [1u32, 2u32] {
+        client.accept_game(&session_id, &codebreaker);
+        client.commit_code(&session_id, &codemaker, &commitment);
+    }
+
+    assert_eq!(
+        client.try_give_hint(&2u32, &codemaker, &0u32, &1u32),
+        Err(Ok(Error::HintsNotAllowed))
+    );
+    assert_eq!(
+        client.try_give_hint(&1u32, &codemaker, &4u32, &1u32),
+        Err(Ok(Error::InvalidGuessValue))
+    );
+
+    client.give_hint(&1u32, &codemaker, &2u32, &5u32);
+    let game = client.get_game(&1u32);
+    assert_eq!(game.hints.len(), 1);
+    assert_eq!(
+        game.hints.get_unchecked(0),
+        Hint {
+            position: 2,
+            color: 5,
+        }
+    );
+
+    // A second hint would leave no guesses at all
+    assert_eq!(
+        client.try_give_hint(&1u32, &codemaker, &0u32, &1u32),
+        Err(Ok(Error::HintLimitReached))
+    );
+
+    // The hint used up one of the two guesses
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &0u32, &proof_hash);
+
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::Finished);
+    assert_eq!(game.winner, Some(codemaker));
+}
+
+#[test]
+fn test_rejected_proof_forfeits_only_strict_games() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let verifier = MockVerifierClient::new(&env, &env.register(MockVerifier, ()));
+    client.set_verifier(&verifier.address);
+
+    let strict = GameConfig {
+        strict_proofs: true,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &test_config(&env));
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &strict);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    for session_id in [1u32, 2u32] {
+        client.accept_game(&session_id, &codebreaker);
+        client.commit_code(&session_id, &codemaker, &commitment);
+        client.submit_guess(&session_id, &codebreaker, &guess);
+    }
+    let proof = Bytes::from_array(&env, &[0x42u8; 64]);
+
+    // Lenient: the proof is refused and the codemaker may retry
+    assert_eq!(
+        client.try_submit_proven_feedback(&1u32, &codemaker, &1u32, &0u32, &proof),
+        Err(Ok(Error::InvalidProof))
+    );
+    assert_eq!(client.get_game(&1u32).phase, GamePhase::WaitingForFeedback);
+    verifier.set_valid(&true);
+    client.submit_proven_feedback(&1u32, &codemaker, &1u32, &0u32, &proof);
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::WaitingForGuess);
+    let proof_hash: BytesN<32> = env.crypto().sha256(&proof).into();
+    assert_eq!(game.feedbacks.get_unchecked(0).proof_hash, proof_hash);
+
+    // Strict: the codemaker forfeits
+    verifier.set_valid(&false);
+    client.submit_proven_feedback(&2u32, &codemaker, &1u32, &0u32, &proof);
+    let game = client.get_game(&2u32);
+    assert_eq!(game.phase, GamePhase::Finished);
+    assert_eq!(game.winner, Some(codebreaker));
+    assert_eq!(game.feedbacks.len(), 0);
+    assert_eq!(client.get_finish_reason(&2u32), FinishReason::Forfeit);
+}
+
+#[test]
+fn test_guesses_until_codemaker_wins() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let config = GameConfig {
+        max_guesses: 2,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    assert_eq!(client.guesses_until_codemaker_wins(&1u32), 2);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    assert_eq!(client.guesses_until_codemaker_wins(&1u32), 1);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &0u32, &proof_hash);
+    assert_eq!(client.guesses_until_codemaker_wins(&1u32), 1);
+
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &0u32, &proof_hash);
+    assert_eq!(client.get_game(&1u32).winner, Some(codemaker));
+    assert_eq!(client.guesses_until_codemaker_wins(&1u32), 0);
+}
+
+#[test]
+fn test_seal_freezes_config_but_not_play() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    assert!(!client.is_sealed());
+    client.seal();
+    assert!(client.is_sealed());
+
+    let other = Address::generate(&env);
+    assert_eq!(client.try_set_admin(&other), Err(Ok(Error::Sealed)));
+    assert_eq!(client.try_set_verifier(&other), Err(Ok(Error::Sealed)));
+    assert_eq!(client.try_set_hub(&other), Err(Ok(Error::Sealed)));
+    assert_eq!(
+        client.try_upgrade(&BytesN::from_array(&env, &[0u8; 32]), &false),
+        Err(Ok(Error::Sealed))
+    );
+    assert_eq!(
+        client.try_new_game(&2u32, &codemaker, &codebreaker),
+        Err(Ok(Error::Sealed))
+    );
+    assert_eq!(client.try_seal(), Err(Ok(Error::Sealed)));
+
+    // The game already in progress plays out and reports as usual
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &4u32, &0u32, &proof_hash);
+    client.report_result(&1u32);
+    assert_eq!(client.get_game(&1u32).winner, Some(codebreaker));
+}
+
+#[test]
+fn test_suggest_next_guess_minimizes_worst_case() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let config = GameConfig {
+        code_length: 3,
+        num_colors: 3,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &0u32, &1u32, &proof_hash);
+
+    // [1,1,2], [1,2,2], [2,1,2] and [2,2,0] remain. Only [1,2,2] and [2,1,2]
+    // tell all four apart; the earlier of the two is suggested.
+    let mut expected = Vec::new(&env);
+    expected.push_back(1u32);
+    expected.push_back(2u32);
+    expected.push_back(2u32);
+    assert_eq!(client.suggest_next_guess(&1u32), expected);
+
+    // Every code of a game this size is more than the search allows
+    let large = GameConfig {
+        code_length: 8,
+        num_colors: 10,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &large);
+    assert_eq!(
+        client.try_suggest_next_guess(&2u32),
+        Err(Ok(Error::InvalidConfig))
+    );
+}
+
+#[test]
+fn test_hub_receives_player_points() {
+    let (env, client, hub, codemaker, codebreaker) = setup_test_with_namespace(0);
+
+    client.new_game(&1u32, &codemaker, &codebreaker);
+    client.accept_game(&1u32, &codebreaker);
+    assert_eq!(hub.last_points(), Some((0, 0)));
+
+    let ratings = MockRatingsClient::new(&env, &env.register(MockRatings, ()));
+    ratings.set_points(&codemaker, &1_500);
+    ratings.set_points(&codebreaker, &1_320);
+    client.set_ratings(&ratings.address);
+    assert_eq!(client.get_ratings(), Some(ratings.address.clone()));
+
+    client.new_game(&2u32, &codemaker, &codebreaker);
+    client.accept_game(&2u32, &codebreaker);
+    assert_eq!(hub.last_started(), Some(2));
+    assert_eq!(hub.last_points(), Some((1_500, 1_320)));
+}
+
+#[test]
+fn test_verify_transcript_rejects_wrong_winner() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &2u32, &1u32, &proof_hash);
+    assert!(client.verify_transcript(&client.export_transcript(&1u32)));
+
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &4u32, &0u32, &proof_hash);
+    let transcript = client.export_transcript(&1u32);
+    assert!(client.verify_transcript(&transcript));
+
+    let mut tampered = transcript.clone();
+    tampered.winner = Some(codemaker);
+    assert!(!client.verify_transcript(&tampered));
+
+    // Play cannot continue past a solving feedback
+    let mut tampered = transcript;
+    tampered.guesses.push_back(guess);
+    tampered
+        .feedbacks
+        .push_back(tampered.feedbacks.get_unchecked(0));
+    assert!(!client.verify_transcript(&tampered));
+}
+
+#[test]
+fn test_color_labels_round_trip() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let mut labels = Vec::new(&env);
+    for name in ["red", "blue", "green", "yellow", "orange", "purple"] {
+        labels.push_back(String::from_str(&env, name));
+    }
+    let config = GameConfig {
+        color_labels: labels.clone(),
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    assert_eq!(client.get_config(&1u32).color_labels, labels);
+
+    labels.pop_back();
+    let mismatched = GameConfig {
+        color_labels: labels,
+        ..test_config(&env)
+    };
+    assert_eq!(
+        client.try_new_game_with_config(&2u32, &codemaker, &codebreaker, &mismatched),
+        Err(Ok(Error::InvalidConfig))
+    );
+}
+
+#[test]
+fn test_settle_many_reports_and_pays_finished_games() {
+    let (env, client, hub, codemaker, codebreaker) = setup_test_with_namespace(0);
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+
+    let config = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 100,
+        codebreaker_stake: 100,
+        ..test_config(&env)
+    };
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    for session_id in [1u32, 2u32, 3u32] {
+        client.new_game_with_config(&session_id, &codemaker, &codebreaker, &config);
+        client.accept_game(&session_id, &codebreaker);
+        client.commit_code(&session_id, &codemaker, &commitment);
+    }
+
+    // Game 1 goes to the codemaker, game 2 to the codebreaker, game 3 is still on
+    client.give_up(&1u32, &codebreaker);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_guess(&2u32, &codebreaker, &guess);
+    client.submit_feedback(&2u32, &codemaker, &4u32, &0u32, &proof_hash);
+
+    let mut session_ids = Vec::new(&env);
+    session_ids.push_back(1u32);
+    session_ids.push_back(2u32);
+    session_ids.push_back(3u32);
+    session_ids.push_back(99u32);
+    assert_eq!(client.settle_many(&session_ids), 2);
+
+    assert_eq!(hub.last_ended(), Some((2, false)));
+    assert!(client.get_game(&1u32).reported && client.get_game(&1u32).settled);
+    assert!(client.get_game(&2u32).reported && client.get_game(&2u32).settled);
+    assert!(!client.get_game(&3u32).settled);
+    assert_eq!(token.balance(&codemaker), 900);
+    assert_eq!(token.balance(&codebreaker), 900);
+    assert_eq!(client.total_escrow(&token.address), 200);
+
+    // Nothing left to do for the finished games
+    assert_eq!(client.settle_many(&session_ids), 0);
+    assert_eq!(
+        client.try_withdraw_winnings(&1u32, &codemaker),
+        Err(Ok(Error::AlreadySettled))
+    );
+}
+
+#[test]
+fn test_needs_my_feedback() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+
+    // 1 and 3 await feedback, 2 awaits a guess, and in 4 the roles are swapped
+    for session_id in [1u32, 2u32, 3u32] {
+        client.new_game_and_commit(&session_id, &codemaker, &codebreaker, &commitment);
+    }
+    client.new_game_and_commit(&4u32, &codebreaker, &codemaker, &commitment);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_guess(&3u32, &codebreaker, &guess);
+    client.submit_guess(&4u32, &codemaker, &guess);
+
+    let mut expected = Vec::new(&env);
+    expected.push_back(1u32);
+    expected.push_back(3u32);
+    assert_eq!(client.needs_my_feedback(&codemaker), expected);
+
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &0u32, &proof_hash);
+    expected.pop_front();
+    assert_eq!(client.needs_my_feedback(&codemaker), expected);
+}
+
+#[test]
+fn test_observer_sees_moves_and_cannot_break_play() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let observer = MockObserverClient::new(&env, &env.register(MockObserver, ()));
+    client.set_observer(&Some(observer.address.clone()));
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &0u32, &proof_hash);
+
+    let mut expected = Vec::new(&env);
+    expected.push_back((1u32, GamePhase::WaitingForGuess, 0u32));
+    expected.push_back((1u32, GamePhase::WaitingForFeedback, 0u32));
+    expected.push_back((1u32, GamePhase::WaitingForGuess, 1u32));
+    assert_eq!(observer.moves(), expected);
+    assert!(!client.observer_failed());
+
+    // A broken observer is flagged, but the move still goes through
+    client.set_observer(&Some(env.register(PanickingObserver, ())));
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    assert_eq!(client.get_game(&1u32).phase, GamePhase::WaitingForFeedback);
+    assert!(client.observer_failed());
+
+    client.set_observer(&None);
+    assert_eq!(client.get_observer(), None);
+    assert!(!client.observer_failed());
+}
+
+#[test]
+fn test_remaining_candidates_shrink_with_feedback() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let config = GameConfig {
+        code_length: 3,
+        num_colors: 3,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    assert_eq!(client.remaining_candidates(&1u32), 27);
+
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &0u32, &1u32, &proof_hash);
+    assert_eq!(client.remaining_candidates(&1u32), 4);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(2u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &2u32, &proof_hash);
+    assert_eq!(client.remaining_candidates(&1u32), 1);
+
+    // Too many codes to count within budget
+    client.new_game(&2u32, &codemaker, &codebreaker);
+    let large = GameConfig {
+        code_length: 8,
+        num_colors: 10,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&3u32, &codemaker, &codebreaker, &large);
+    assert_eq!(client.remaining_candidates(&2u32), 1_296);
+    assert_eq!(client.remaining_candidates(&3u32), u32::MAX);
+}
+
+#[test]
+fn test_split_and_combined_feedback_modes() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let combined = GameConfig {
+        feedback_mode: FEEDBACK_COMBINED,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &test_config(&env));
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &combined);
+    assert_eq!(client.get_config(&2u32).feedback_mode, FEEDBACK_COMBINED);
+    let bad_mode = GameConfig {
+        feedback_mode: 2,
+        ..test_config(&env)
+    };
+    assert_eq!(
+        client.try_new_game_with_config(&3u32, &codemaker, &codebreaker, &bad_mode),
+        Err(Ok(Error::InvalidConfig))
+    );
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    for session_id in [1u32, 2u32] {
+        client.accept_game(&session_id, &codebreaker);
+        client.commit_code(&session_id, &codemaker, &commitment);
+        client.submit_guess(&session_id, &codebreaker, &guess);
+    }
+
+    // Split mode keeps red and white apart and has no score entrypoint
+    assert_eq!(
+        client.try_submit_score(&1u32, &codemaker, &3u32, &false, &proof_hash),
+        Err(Ok(Error::InvalidFeedback))
+    );
+    client.submit_feedback(&1u32, &codemaker, &1u32, &2u32, &proof_hash);
+    let feedback = client.get_last_feedback(&1u32);
+    assert_eq!((feedback.correct_position, feedback.correct_color), (1, 2));
+
+    // Combined mode takes one score, bounded by the code length
+    assert_eq!(
+        client.try_submit_feedback(&2u32, &codemaker, &1u32, &2u32, &proof_hash),
+        Err(Ok(Error::InvalidFeedback))
+    );
+    assert_eq!(
+        client.try_submit_score(&2u32, &codemaker, &5u32, &false, &proof_hash),
+        Err(Ok(Error::InvalidFeedback))
+    );
+    client.submit_score(&2u32, &codemaker, &3u32, &false, &proof_hash);
+    let feedback = client.get_last_feedback(&2u32);
+    assert_eq!((feedback.correct_position, feedback.correct_color), (3, 0));
+    assert_eq!(client.get_game(&2u32).phase, GamePhase::WaitingForGuess);
+}
+
+#[test]
+fn test_upgrade_blocked_by_outstanding_escrow() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+    // Not an uploaded wasm, so an upgrade that passes the guard fails at the swap
+    let wasm_hash = BytesN::from_array(&env, &[0x11u8; 32]);
+    let passes_guard = |result: Result<Result<(), _>, Result<Error, _>>| {
+        result != Err(Ok(Error::EscrowOutstanding))
+    };
+
+    assert!(passes_guard(client.try_upgrade(&wasm_hash, &false)));
+
+    let config = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 100,
+        codebreaker_stake: 100,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    assert_eq!(
+        client.try_upgrade(&wasm_hash, &false),
+        Err(Ok(Error::EscrowOutstanding))
+    );
+    assert!(passes_guard(client.try_upgrade(&wasm_hash, &true)));
+
+    // Once the stakes are refunded the escrow is clear again
+    let move_timeout = client.get_timeout_config().move_timeout;
+    env.ledger().set_sequence_number(move_timeout + 1);
+    client.claim_commit_timeout(&1u32, &codebreaker);
+    assert_eq!(client.total_escrow(&token.address), 0);
+    assert!(passes_guard(client.try_upgrade(&wasm_hash, &false)));
+}
+
+#[test]
+fn test_phase_name() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let name = |s: &str| String::from_str(&env, s);
+
+    client.new_game(&1u32, &codemaker, &codebreaker);
+    assert_eq!(client.phase_name(&1u32), name("WaitingForAccept"));
+    client.accept_game(&1u32, &codebreaker);
+    assert_eq!(client.phase_name(&1u32), name("WaitingForCommitment"));
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    assert_eq!(client.phase_name(&1u32), name("WaitingForGuess"));
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    assert_eq!(client.phase_name(&1u32), name("WaitingForFeedback"));
+    client.give_up(&1u32, &codebreaker);
+    assert_eq!(client.phase_name(&1u32), name("Finished"));
+}
+
+#[test]
+fn test_active_game_limit_per_player() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    assert_eq!(client.get_max_games_per_player(), 10);
+    client.set_max_games_per_player(&2u32);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    client.new_game(&2u32, &codemaker, &codebreaker);
+
+    // Both players are at the cap, whichever role they would take
+    let other = Address::generate(&env);
+    assert_eq!(
+        client.try_new_game(&3u32, &codemaker, &other),
+        Err(Ok(Error::TooManyActiveGames))
+    );
+    assert_eq!(
+        client.try_new_game(&3u32, &other, &codebreaker),
+        Err(Ok(Error::TooManyActiveGames))
+    );
+
+    // Finishing a game frees the slot
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.give_up(&1u32, &codebreaker);
+    client.new_game(&3u32, &codemaker, &other);
+}
+
+#[test]
+fn test_cancel_stale_games() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+
+    // 1 never gets accepted, 2 never gets a guess, 3 is under way
+    let staked = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 300,
+        codebreaker_stake: 200,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &staked);
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &staked);
+    client.accept_game(&2u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&3u32, &codemaker, &codebreaker, &commitment);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_guess(&3u32, &codebreaker, &guess);
+    client.submit_feedback(&3u32, &codemaker, &1u32, &0u32, &proof_hash);
+
+    assert_eq!(client.cancel_stale(&codebreaker), 0);
+
+    let window = client.get_timeout_config().abandonment_window;
+    env.ledger().set_sequence_number(window + 1);
+    assert_eq!(client.cancel_stale(&codebreaker), 2);
+
+    assert_eq!(client.try_get_game(&1u32), Err(Ok(Error::GameNotFound)));
+    assert_eq!(client.try_get_game(&2u32), Err(Ok(Error::GameNotFound)));
+    assert_eq!(client.get_game(&3u32).phase, GamePhase::WaitingForGuess);
+    assert_eq!(token.balance(&codemaker), 1_000);
+    assert_eq!(token.balance(&codebreaker), 1_000);
+    assert_eq!(client.total_escrow(&token.address), 0);
+}
+
+#[test]
+fn test_solver_state_mid_game() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let config = GameConfig {
+        code_length: 3,
+        num_colors: 5,
+        max_guesses: 6,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(4u32);
+    guess.push_back(4u32);
+    guess.push_back(0u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &1u32, &proof_hash);
+
+    let mut guesses = Vec::new(&env);
+    guesses.push_back(guess);
+    let mut feedbacks = Vec::new(&env);
+    feedbacks.push_back(Feedback {
+        correct_position: 1,
+        correct_color: 1,
+        proof_hash,
+    });
+    assert_eq!(
+        client.get_solver_state(&1u32),
+        SolverState {
+            code_length: 3,
+            num_colors: 5,
+            allow_duplicates: true,
+            feedback_mode: FEEDBACK_SPLIT,
+            guesses,
+            feedbacks,
+            remaining_guesses: 5,
+        }
+    );
+}
+
+#[test]
+fn test_last_chance_fires_on_penultimate_feedback() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let config = GameConfig {
+        max_guesses: 3,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    let last_chance: Vec<Val> = (
+        symbol_short!("game"),
+        Symbol::new(&env, "last_chance"),
+        1u32,
+    )
+        .into_val(&env);
+
+    // Only the second of three feedbacks leaves exactly one guess
+    for round in 0..3 {
+        client.submit_guess(&1u32, &codebreaker, &guess);
+        client.submit_feedback(&1u32, &codemaker, &1u32, &1u32, &proof_hash);
+        let fired = env
+            .events()
+            .all()
+            .iter()
+            .any(|(_, topics, _)| topics == last_chance);
+        assert_eq!(fired, round == 1);
+    }
+}
+
+#[test]
+fn test_guess_agent_submits_for_codebreaker() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+
+    let agent = Address::generate(&env);
+    let result = client.try_submit_guess(&1u32, &agent, &guess);
+    assert_eq!(result, Err(Ok(Error::NotCodeBreaker)));
+
+    // Only the codebreaker can appoint an agent
+    let result = client.try_authorize_guess_agent(&1u32, &codemaker, &agent);
+    assert_eq!(result, Err(Ok(Error::NotCodeBreaker)));
+
+    client.authorize_guess_agent(&1u32, &codebreaker, &agent);
+    client.submit_guess(&1u32, &agent, &guess);
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::WaitingForFeedback);
+    assert_eq!(game.current_guess, guess);
+
+    let stranger = Address::generate(&env);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &1u32, &proof_hash);
+    let result = client.try_submit_guess(&1u32, &stranger, &guess);
+    assert_eq!(result, Err(Ok(Error::NotCodeBreaker)));
+}
+
+#[test]
+fn test_verifier_for_config_is_preferred() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let global = MockVerifierClient::new(&env, &env.register(MockVerifier, ()));
+    let wide = MockVerifierClient::new(&env, &env.register(MockVerifier, ()));
+    wide.set_valid(&true);
+    client.set_verifier(&global.address);
+    client.set_verifier_for(&5u32, &8u32, &wide.address);
+
+    assert_eq!(client.get_verifier_for(&5u32, &8u32), wide.address);
+    assert_eq!(client.get_verifier_for(&4u32, &6u32), global.address);
+
+    let config = GameConfig {
+        code_length: 5,
+        num_colors: 8,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &test_config(&env));
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let proof = Bytes::from_array(&env, &[0x42u8; 64]);
+    for (session_id, len) in [(1u32, 5u32), (2u32, 4u32)] {
+        let mut guess = Vec::new(&env);
+        for color in 0..len {
+            guess.push_back(color);
+        }
+        client.accept_game(&session_id, &codebreaker);
+        client.commit_code(&session_id, &codemaker, &commitment);
+        client.submit_guess(&session_id, &codebreaker, &guess);
+    }
+
+    // Only the 5x8 game reaches the verifier that accepts the proof
+    client.submit_proven_feedback(&1u32, &codemaker, &1u32, &0u32, &proof);
+    assert_eq!(client.get_game(&1u32).feedbacks.len(), 1);
+    assert_eq!(
+        client.try_submit_proven_feedback(&2u32, &codemaker, &1u32, &0u32, &proof),
+        Err(Ok(Error::InvalidProof))
+    );
+}
+
+#[test]
+fn test_resign_penalty_slashes_to_treasury() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+    let treasury = Address::generate(&env);
+    client.set_treasury(&treasury);
+    client.set_resign_penalty(&2_500u32);
+    assert_eq!(
+        client.try_set_resign_penalty(&10_001u32),
+        Err(Ok(Error::InvalidConfig))
+    );
+
+    let config = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 300,
+        codebreaker_stake: 400,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    client.give_up(&1u32, &codebreaker);
+
+    // A quarter of the codebreaker's 400 goes to the treasury
+    assert_eq!(client.withdraw_winnings(&1u32, &codemaker), 600);
+    assert_eq!(token.balance(&treasury), 100);
+    assert_eq!(token.balance(&codemaker), 1_300);
+    assert_eq!(token.balance(&codebreaker), 600);
+    assert_eq!(token.balance(&client.address), 0);
+    assert_eq!(client.total_escrow(&token.address), 0);
+}
+
+#[test]
+fn test_get_addresses_matches_constructor() {
+    let (env, client, hub, _codemaker, _codebreaker) = setup_test_with_namespace(0);
+
+    let mut expected = Addresses {
+        admin: client.get_admin(),
+        game_hub: hub.address.clone(),
+        verifier: client.get_verifier(),
+        treasury: None,
+    };
+    assert_eq!(client.get_addresses(), expected);
+
+    let treasury = Address::generate(&env);
+    client.set_treasury(&treasury);
+    expected.treasury = Some(treasury);
+    assert_eq!(client.get_addresses(), expected);
+}
+
+#[test]
+fn test_seed_commit_reveal_cycle() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    client.new_game(&1u32, &codemaker, &codebreaker);
+    client.accept_game(&1u32, &codebreaker);
+
+    let seed = BytesN::from_array(&env, &[0x5Eu8; 32]);
+    let seed_hash: BytesN<32> = env.crypto().sha256(&seed.clone().into()).into();
+    client.commit_seed(&1u32, &codemaker, &seed_hash);
+    assert_eq!(
+        client.try_commit_seed(&1u32, &codemaker, &seed_hash),
+        Err(Ok(Error::InvalidPhase))
+    );
+
+    // The codemaker derives the secret once the entropy is fixed. It is not
+    // the predictable ledger anchor alone.
+    let game = client.get_game(&1u32);
+    let entropy = game.fair_play.seed_entropy.unwrap();
+    assert_ne!(entropy, game.randomness_anchor);
+    let mut preimage = Bytes::from_array(&env, &seed.to_array());
+    preimage.extend_from_array(&entropy.to_array());
+    let digest = env.crypto().sha256(&preimage).to_array();
+    let mut secret = Vec::new(&env);
+    for byte in &digest[..4] {
+        secret.push_back(*byte as u32 % 6);
+    }
+    let salt = BytesN::from_array(&env, &[0x11u8; 32]);
+    client.commit_code(&1u32, &codemaker, &sha256_commitment(&env, &secret, &salt));
+    client.give_up(&1u32, &codebreaker);
+
+    let wrong = BytesN::from_array(&env, &[0x5Fu8; 32]);
+    assert_eq!(
+        client.try_reveal_seed(&1u32, &codemaker, &wrong, &salt),
+        Err(Ok(Error::CommitmentMismatch))
+    );
+    client.reveal_seed(&1u32, &codemaker, &seed, &salt);
+    assert_eq!(client.get_revealed_secret(&1u32), secret);
+}
+
+#[test]
+fn test_reveal_seed_checks_commitment() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    client.new_game(&1u32, &codemaker, &codebreaker);
+    client.accept_game(&1u32, &codebreaker);
+
+    let seed = BytesN::from_array(&env, &[0x5Eu8; 32]);
+    let seed_hash: BytesN<32> = env.crypto().sha256(&seed.clone().into()).into();
+    client.commit_seed(&1u32, &codemaker, &seed_hash);
+
+    // A secret chosen freely instead of derived from the seed is caught
+    let mut secret = Vec::new(&env);
+    for peg in 0..4u32 {
+        secret.push_back(peg);
+    }
+    let salt = BytesN::from_array(&env, &[0x11u8; 32]);
+    client.commit_code(&1u32, &codemaker, &sha256_commitment(&env, &secret, &salt));
+    client.give_up(&1u32, &codebreaker);
+
+    assert_eq!(
+        client.try_reveal_seed(&1u32, &codemaker, &seed, &salt),
+        Err(Ok(Error::CommitmentMismatch))
+    );
+    // Nor can it be opened directly, skipping the seed
+    assert_eq!(
+        client.try_reveal_code(&1u32, &codemaker, &secret, &salt),
+        Err(Ok(Error::InvalidReveal))
+    );
+    assert!(!client.get_game(&1u32).revealed);
+}
+
+#[test]
+fn test_time_in_current_phase() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    env.ledger().set_sequence_number(100);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    assert_eq!(client.time_in_current_phase(&1u32), 0);
+
+    env.ledger().set_sequence_number(300);
+    assert_eq!(client.time_in_current_phase(&1u32), 200);
+
+    // A transition restarts the count
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    env.ledger().set_sequence_number(350);
+    assert_eq!(client.time_in_current_phase(&1u32), 50);
+}
+
+#[test]
+fn test_strict_game_rejects_reused_proof_hash() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let strict = GameConfig {
+        strict_proofs: true,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &test_config(&env));
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &strict);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    for session_id in [1u32, 2u32] {
+        client.accept_game(&session_id, &codebreaker);
+        client.commit_code(&session_id, &codemaker, &commitment);
+        client.submit_guess(&session_id, &codebreaker, &guess);
+        assert!(!client.has_seen_proof(&session_id, &proof_hash));
+        client.submit_feedback(&session_id, &codemaker, &1u32, &1u32, &proof_hash);
+        assert!(client.has_seen_proof(&session_id, &proof_hash));
+        client.submit_guess(&session_id, &codebreaker, &guess);
+    }
+
+    // Lenient games only record the hash
+    client.submit_feedback(&1u32, &codemaker, &1u32, &1u32, &proof_hash);
+    assert_eq!(client.get_game(&1u32).feedbacks.len(), 2);
+
+    assert_eq!(
+        client.try_submit_feedback(&2u32, &codemaker, &1u32, &1u32, &proof_hash),
+        Err(Ok(Error::DuplicateProof))
+    );
+    let fresh = BytesN::from_array(&env, &[0xCEu8; 32]);
+    client.submit_feedback(&2u32, &codemaker, &1u32, &1u32, &fresh);
+    assert_eq!(client.get_game(&2u32).feedbacks.len(), 2);
+}
+
+#[test]
+fn test_games_in_phase() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    for session_id in 1u32..=5 {
+        client.new_game_and_commit(&session_id, &codemaker, &codebreaker, &commitment);
+    }
+    client.new_game(&6u32, &codemaker, &codebreaker);
+    for session_id in [2u32, 3u32, 5u32] {
+        client.submit_guess(&session_id, &codebreaker, &guess);
+    }
+    client.give_up(&4u32, &codebreaker);
+
+    let ids = |list: &[u32]| {
+        let mut v = Vec::new(&env);
+        for id in list {
+            v.push_back(*id);
+        }
+        v
+    };
+    assert_eq!(
+        client.games_in_phase(&GamePhase::WaitingForFeedback, &0u32, &10u32),
+        ids(&[2, 3, 5])
+    );
+    assert_eq!(
+        client.games_in_phase(&GamePhase::WaitingForGuess, &0u32, &10u32),
+        ids(&[1])
+    );
+    assert_eq!(
+        client.games_in_phase(&GamePhase::WaitingForAccept, &0u32, &10u32),
+        ids(&[6])
+    );
+
+    // Finished games leave the index
+    assert_eq!(
+        client.games_in_phase(&GamePhase::Finished, &0u32, &10u32),
+        ids(&[])
+    );
+
+    // Pagination counts matching games only
+    assert_eq!(
+        client.games_in_phase(&GamePhase::WaitingForFeedback, &1u32, &1u32),
+        ids(&[3])
+    );
+}
+
+#[test]
+fn test_strict_reveal_catches_false_feedback() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let salt = BytesN::from_array(&env, &[0x11u8; 32]);
+
+    let mut secret = Vec::new(&env);
+    secret.push_back(0u32);
+    secret.push_back(1u32);
+    secret.push_back(2u32);
+    secret.push_back(3u32);
+    let commitment = sha256_commitment(&env, &secret, &salt);
+    let strict = GameConfig {
+        strict_proofs: true,
+        ..test_config(&env)
+    };
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(3u32);
+    guess.push_back(1u32);
+    guess.push_back(4u32);
+    guess.push_back(4u32);
+    // The true answer is 1 red, 1 white
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    for (session_id, red, white) in [(1u32, 1u32, 1u32), (2u32, 0u32, 1u32)] {
+        client.new_game_with_config(&session_id, &codemaker, &codebreaker, &strict);
+        client.accept_game(&session_id, &codebreaker);
+        client.commit_code(&session_id, &codemaker, &commitment);
+        client.submit_guess(&session_id, &codebreaker, &guess);
+        client.submit_feedback(&session_id, &codemaker, &red, &white, &proof_hash);
+        client.give_up(&session_id, &codebreaker);
+        client.reveal_code(&session_id, &codemaker, &secret, &salt);
+    }
+
+    let (_, topics, data) = env.events().all().last().unwrap();
+    let expected_topics: Vec<Val> =
+        (symbol_short!("game"), symbol_short!("forfeited"), 2u32).into_val(&env);
+    assert_eq!(topics, expected_topics);
+    let (winner, loser) = <(Address, Address)>::try_from_val(&env, &data).unwrap();
+    assert_eq!(winner, codebreaker);
+    assert_eq!(loser, codemaker);
+
+    let honest = client.get_game(&1u32);
+    assert_eq!(honest.winner, Some(codemaker.clone()));
+    assert_eq!(honest.finish_reason, FinishReason::Resigned);
+
+    let caught = client.get_game(&2u32);
+    assert_eq!(caught.winner, Some(codebreaker));
+    assert_eq!(caught.finish_reason, FinishReason::FalseFeedback);
+    assert!(client.verify_transcript(&client.export_transcript(&2u32)));
+}
+
+#[test]
+fn test_new_game_idempotent_creates_once() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+
+    let config = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 300,
+        codebreaker_stake: 300,
+        ..test_config(&env)
+    };
+    let key = BytesN::from_array(&env, &[0x1Du8; 32]);
+    let first = client.new_game_idempotent(&1u32, &codemaker, &codebreaker, &config, &key);
+    assert_eq!(first, 1);
+    client.accept_game(&1u32, &codebreaker);
+
+    // A retry returns the same game instead of overwriting it
+    let retry = client.new_game_idempotent(&2u32, &codemaker, &codebreaker, &config, &key);
+    assert_eq!(retry, 1);
+    assert_eq!(client.try_get_game(&2u32), Err(Ok(Error::GameNotFound)));
+    assert_eq!(
+        client.get_game(&1u32).phase,
+        GamePhase::WaitingForCommitment
+    );
+    assert_eq!(token.balance(&codemaker), 700);
+
+    let other = BytesN::from_array(&env, &[0x2Du8; 32]);
+    assert_eq!(
+        client.new_game_idempotent(&2u32, &codemaker, &codebreaker, &config, &other),
+        2
+    );
+
+    // Someone else reusing a seen key gets their own game, not ours
+    let squatter = Address::generate(&env);
+    let unstaked = test_config(&env);
+    let squatted = BytesN::from_array(&env, &[0x3Du8; 32]);
+    assert_eq!(
+        client.new_game_idempotent(&3u32, &squatter, &codebreaker, &unstaked, &squatted),
+        3
+    );
+    assert_eq!(
+        client.new_game_idempotent(&4u32, &codemaker, &codebreaker, &unstaked, &squatted),
+        4
+    );
+    assert_eq!(client.get_game(&4u32).codemaker, codemaker);
+}
+
+#[test]
+fn test_player_history_records_wins_and_losses() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    for session_id in 1u32..=4 {
+        client.new_game_and_commit(&session_id, &codemaker, &codebreaker, &commitment);
+    }
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &4u32, &0u32, &proof_hash);
+    client.give_up(&2u32, &codebreaker);
+    // Game 3 is still in play
+    client.submit_guess(&4u32, &codebreaker, &guess);
+    client.submit_feedback(&4u32, &codemaker, &4u32, &0u32, &proof_hash);
+
+    let mut expected = Vec::new(&env);
+    expected.push_back((4u32, FinishReason::Solved, true));
+    expected.push_back((2u32, FinishReason::Resigned, false));
+    expected.push_back((1u32, FinishReason::Solved, true));
+    assert_eq!(
+        client.get_player_history(&codebreaker, &0u32, &10u32),
+        expected
+    );
+
+    let mut page = Vec::new(&env);
+    page.push_back((2u32, FinishReason::Resigned, true));
+    assert_eq!(client.get_player_history(&codemaker, &1u32, &1u32), page);
+}
+
+#[test]
+fn test_history_root_accumulates_finished_games() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    assert_eq!(
+        client.get_history_root(),
+        BytesN::from_array(&env, &[0u8; 32])
+    );
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let pair = |left: &BytesN<32>, right: &BytesN<32>| -> BytesN<32> {
+        let mut preimage = Bytes::from_array(&env, &left.to_array());
+        preimage.extend_from_array(&right.to_array());
+        env.crypto().sha256(&preimage).into()
+    };
+
+    let mut leaves = Vec::new(&env);
+    for session_id in 1u32..=3 {
+        client.new_game_and_commit(&session_id, &codemaker, &codebreaker, &commitment);
+        client.give_up(&session_id, &codebreaker);
+        leaves.push_back(client.result_hash(&session_id));
+    }
+    let (h1, h2, h3) = (
+        leaves.get_unchecked(0),
+        leaves.get_unchecked(1),
+        leaves.get_unchecked(2),
+    );
+
+    // Two leaves merge into one peak, the third stands alone
+    assert_eq!(client.get_history_root(), pair(&pair(&h1, &h2), &h3));
+
+    // An unfinished game leaves the root alone
+    client.new_game_and_commit(&4u32, &codemaker, &codebreaker, &commitment);
+    assert_eq!(client.get_history_root(), pair(&pair(&h1, &h2), &h3));
+    client.give_up(&4u32, &codebreaker);
+    let h4 = client.result_hash(&4u32);
+    assert_eq!(
+        client.get_history_root(),
+        pair(&pair(&h1, &h2), &pair(&h3, &h4))
+    );
+}
+
+#[test]
+fn test_commit_with_validity_proof() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let config = GameConfig {
+        validity_proof: true,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    assert_eq!(
+        client.try_commit_code(&1u32, &codemaker, &commitment),
+        Err(Ok(Error::MissingProof))
+    );
+    let no_proof = BytesN::from_array(&env, &[0u8; 32]);
+    assert_eq!(
+        client.try_commit_code_with_validity_proof(&1u32, &codemaker, &commitment, &no_proof),
+        Err(Ok(Error::MissingProof))
+    );
+
+    let proof_hash = BytesN::from_array(&env, &[0xEFu8; 32]);
+    client.commit_code_with_validity_proof(&1u32, &codemaker, &commitment, &proof_hash);
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::WaitingForGuess);
+    assert_eq!(game.commitment, commitment);
+    assert_eq!(game.fair_play.validity_proof_hash, Some(proof_hash));
+}
+
+#[test]
+fn test_status_reflects_pause_and_seal() {
+    let (_env, client, codemaker, codebreaker) = setup_test();
+
+    let mut expected = ContractStatus {
+        paused: false,
+        sealed: false,
+        accepting_games: true,
+        version: 1,
+    };
+    assert_eq!(client.get_status(), expected);
+
+    client.set_paused(&true);
+    expected.paused = true;
+    expected.accepting_games = false;
+    assert_eq!(client.get_status(), expected);
+    assert_eq!(
+        client.try_new_game(&1u32, &codemaker, &codebreaker),
+        Err(Ok(Error::Paused))
+    );
+
+    client.set_paused(&false);
+    expected.paused = false;
+    expected.accepting_games = true;
+    assert_eq!(client.get_status(), expected);
+    client.new_game(&1u32, &codemaker, &codebreaker);
+
+    client.seal();
+    expected.sealed = true;
+    expected.accepting_games = false;
+    assert_eq!(client.get_status(), expected);
+    assert_eq!(client.try_set_paused(&true), Err(Ok(Error::Sealed)));
+}
+
+#[test]
+fn test_proof_binding_rejects_other_session() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    for (session_id, byte) in [(1u32, 0xABu8), (2u32, 0xBAu8)] {
+        let commitment = BytesN::from_array(&env, &[byte; 32]);
+        client.new_game_and_commit(&session_id, &codemaker, &codebreaker, &commitment);
+        client.submit_guess(&session_id, &codebreaker, &guess);
+    }
+
+    let field = |value: u32| {
+        let mut bytes = [0u8; 32];
+        bytes[28..].copy_from_slice(&value.to_be_bytes());
+        BytesN::from_array(&env, &bytes)
+    };
+    let inputs_for = |commitment: BytesN<32>| {
+        let mut inputs = Vec::new(&env);
+        inputs.push_back(commitment);
+        for value in guess.iter().chain([1, 1]) {
+            inputs.push_back(field(value));
+        }
+        inputs
+    };
+
+    let own = inputs_for(client.get_game(&1u32).commitment);
+    let other = inputs_for(client.get_game(&2u32).commitment);
+    assert!(client.validate_proof_binding(&1u32, &own));
+    assert!(!client.validate_proof_binding(&1u32, &other));
+
+    // Same commitment, but for a guess that was never made
+    let mut wrong_guess = own.clone();
+    wrong_guess.set(1, field(5));
+    assert!(!client.validate_proof_binding(&1u32, &wrong_guess));
+}
+
+/// Seal as `seal_feedback` expects it: sha256 of both peg counts as
+/// big-endian u32, followed by the nonce.
+fn feedback_seal(env: &Env, red: u32, white: u32, nonce: &BytesN<32>) -> BytesN<32> {
+    let mut preimage = Bytes::new(env);
+    preimage.extend_from_array(&red.to_be_bytes());
+    preimage.extend_from_array(&white.to_be_bytes());
+    preimage.extend_from_array(&nonce.to_array());
+    env.crypto().sha256(&preimage).into()
+}
+
+#[test]
+fn test_feedback_timeout_opens_sealed_feedback_or_awards_win() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+
+    let config = GameConfig {
+        sealed_feedback_allowed: true,
+        ..test_config(&env)
+    };
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    for session_id in [1u32, 2u32] {
+        client.new_game_with_config(&session_id, &codemaker, &codebreaker, &config);
+        client.accept_game(&session_id, &codebreaker);
+        client.commit_code(&session_id, &codemaker, &commitment);
+        client.submit_guess(&session_id, &codebreaker, &guess);
+    }
+    let nonce = BytesN::from_array(&env, &[0x77u8; 32]);
+    let seal = feedback_seal(&env, 2, 1, &nonce);
+    client.seal_feedback(&1u32, &codemaker, &seal);
+
+    let feedback_timeout = client.get_timeout_config().feedback_timeout;
+    env.ledger().set_sequence_number(100 + feedback_timeout);
+    assert_eq!(
+        client.try_open_sealed_feedback(&1u32, &codebreaker, &2u32, &1u32, &nonce),
+        Err(Ok(Error::TimeoutNotReached))
+    );
+    env.ledger().set_sequence_number(100 + feedback_timeout + 1);
+
+    // Sealed: the codebreaker reveals the true feedback and play goes on
+    assert_eq!(
+        client.try_claim_feedback_timeout(&1u32, &codebreaker),
+        Err(Ok(Error::TimeoutNotReached))
+    );
+    assert_eq!(
+        client.try_open_sealed_feedback(&1u32, &codebreaker, &3u32, &0u32, &nonce),
+        Err(Ok(Error::CommitmentMismatch))
+    );
+    client.open_sealed_feedback(&1u32, &codebreaker, &2u32, &1u32, &nonce);
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::WaitingForGuess);
+    assert_eq!(
+        game.feedbacks.last().unwrap(),
+        Feedback {
+            correct_position: 2,
+            correct_color: 1,
+            proof_hash: seal,
+        }
+    );
+    assert_eq!(game.fair_play.sealed_feedback, None);
+
+    // Nothing sealed: the codebreaker takes the win
+    client.claim_feedback_timeout(&2u32, &codebreaker);
+    let game = client.get_game(&2u32);
+    assert_eq!(game.phase, GamePhase::Finished);
+    assert_eq!(game.winner, Some(codebreaker));
+    assert_eq!(game.finish_reason, FinishReason::Timeout);
+}
+
+#[test]
+fn test_recent_events_buffer_is_bounded() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &1u32, &proof_hash);
+
+    let mut expected = Vec::new(&env);
+    expected.push_back(EventRecord {
+        kind: EventKind::Feedback,
+        session_id: 1,
+        ledger: 100,
+    });
+    expected.push_back(EventRecord {
+        kind: EventKind::Guess,
+        session_id: 1,
+        ledger: 100,
+    });
+    assert_eq!(client.recent_events(&10u32), expected);
+    assert_eq!(client.recent_events(&1u32).len(), 1);
+
+    // Each game below adds a guess and a give-up; the oldest records drop out
+    for session_id in 2u32..=60 {
+        client.new_game_and_commit(&session_id, &codemaker, &codebreaker, &commitment);
+        client.submit_guess(&session_id, &codebreaker, &guess);
+        client.give_up(&session_id, &codebreaker);
+    }
+    let recent = client.recent_events(&1_000u32);
+    assert_eq!(recent.len(), 100);
+    assert_eq!(
+        recent.first_unchecked(),
+        EventRecord {
+            kind: EventKind::GaveUp,
+            session_id: 60,
+            ledger: 100,
+        }
+    );
+    assert_eq!(recent.last_unchecked().session_id, 11);
+}
+
+#[test]
+fn test_encrypted_secret_round_trips() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    client.new_game(&1u32, &codemaker, &codebreaker);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+
+    let oversized = Bytes::from_array(&env, &[7u8; 257]);
+    assert_eq!(
+        client.try_commit_code_encrypted(&1u32, &codemaker, &commitment, &oversized),
+        Err(Ok(Error::BlobTooLarge))
+    );
+
+    let blob = Bytes::from_array(&env, &[0x5Au8; 48]);
+    client.commit_code_encrypted(&1u32, &codemaker, &commitment, &blob);
+
+    let decryption_key = Bytes::from_array(&env, &[0x11u8; 32]);
+    // The key stays private until the game is over
+    assert_eq!(
+        client.try_decrypt_key_reveal(&1u32, &codemaker, &decryption_key),
+        Err(Ok(Error::InvalidPhase))
+    );
+
+    client.give_up(&1u32, &codebreaker);
+    client.decrypt_key_reveal(&1u32, &codemaker, &decryption_key);
+    assert_eq!(
+        client.try_decrypt_key_reveal(&1u32, &codemaker, &decryption_key),
+        Err(Ok(Error::AlreadyRevealed))
+    );
+
+    let game = client.get_game(&1u32);
+    assert_eq!(game.commitment, commitment);
+    assert_eq!(game.fair_play.encrypted_secret, Some(blob));
+    assert_eq!(game.fair_play.decryption_key, Some(decryption_key));
+}
+
+#[test]
+fn test_color_knowledge_proves_absent_colors() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    // Secret is [2, 3, 4, 5]
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+
+    let mut knowledge = Vec::new(&env);
+    for _ in 0..6 {
+        knowledge.push_back(0i32);
+    }
+    assert_eq!(client.color_knowledge(&1u32), knowledge);
+
+    // [0, 0, 1, 1] scores nothing: neither 0 nor 1 is in the secret
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(1u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &0u32, &0u32, &proof_hash);
+
+    let mut knowledge = Vec::new(&env);
+    for value in [-1i32, -1, 0, 0, 0, 0] {
+        knowledge.push_back(value);
+    }
+    assert_eq!(client.color_knowledge(&1u32), knowledge);
+
+    // [2, 2, 2, 2] scores one red: exactly one 2
+    let mut guess = Vec::new(&env);
+    for _ in 0..4 {
+        guess.push_back(2u32);
+    }
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &0u32, &proof_hash);
+
+    let mut knowledge = Vec::new(&env);
+    for value in [-1i32, -1, 1, 0, 0, 0] {
+        knowledge.push_back(value);
+    }
+    assert_eq!(client.color_knowledge(&1u32), knowledge);
+}
+
+#[test]
+fn test_dispute_window_delays_report() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+    client.set_timeout_config(&TimeoutConfig {
+        dispute_window: 50,
+        ..client.get_timeout_config()
+    });
+    let salt = BytesN::from_array(&env, &[0x11u8; 32]);
+
+    let mut secret = Vec::new(&env);
+    secret.push_back(0u32);
+    secret.push_back(1u32);
+    secret.push_back(2u32);
+    secret.push_back(3u32);
+    let commitment = sha256_commitment(&env, &secret, &salt);
+    client.new_game(&1u32, &codemaker, &codebreaker);
+    client.accept_game(&1u32, &codebreaker);
+    client.commit_code(&1u32, &codemaker, &commitment);
+
+    // Both guesses truly score 1 red, 1 white; the second answer is a lie
+    let mut guess = Vec::new(&env);
+    guess.push_back(3u32);
+    guess.push_back(1u32);
+    guess.push_back(4u32);
+    guess.push_back(4u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &1u32, &proof_hash);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    let proof_hash = BytesN::from_array(&env, &[0xCEu8; 32]);
+    client.submit_feedback(&1u32, &codemaker, &0u32, &1u32, &proof_hash);
+    client.give_up(&1u32, &codebreaker);
+
+    assert_eq!(
+        client.try_report_result(&1u32),
+        Err(Ok(Error::DisputeWindowOpen))
+    );
+    assert_eq!(client.settle_many(&Vec::from_array(&env, [1u32])), 0);
+
+    // Nothing to dispute until the secret is revealed
+    assert_eq!(
+        client.try_dispute_feedback(&1u32, &codebreaker, &1u32),
+        Err(Ok(Error::InvalidPhase))
+    );
+    client.reveal_code(&1u32, &codemaker, &secret, &salt);
+    assert_eq!(client.get_stats(&codemaker).honesty, 1);
+    assert_eq!(
+        client.try_dispute_feedback(&1u32, &codebreaker, &0u32),
+        Err(Ok(Error::InvalidFeedback))
+    );
+    client.dispute_feedback(&1u32, &codebreaker, &1u32);
+    let stats = client.get_stats(&codemaker);
+    assert_eq!((stats.clean_reveals, stats.caught_lies), (0, 1));
+    assert_eq!(stats.honesty, -1);
+
+    let game = client.get_game(&1u32);
+    assert_eq!(game.winner, Some(codebreaker.clone()));
+    assert_eq!(game.finish_reason, FinishReason::FalseFeedback);
+
+    env.ledger().set_sequence_number(150);
+    assert_eq!(
+        client.try_dispute_feedback(&1u32, &codebreaker, &1u32),
+        Err(Ok(Error::InvalidPhase))
+    );
+    client.report_result(&1u32);
+    assert!(client.get_game(&1u32).reported);
+    assert_eq!(
+        client.try_report_result(&1u32),
+        Err(Ok(Error::AlreadyReported))
+    );
+}
+
+#[test]
+fn test_adjust_max_guesses_extends_game() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let config = GameConfig {
+        max_guesses: 2,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &0u32, &proof_hash);
+
+    // The limit only goes up
+    assert_eq!(
+        client.try_adjust_max_guesses(&1u32, &2u32),
+        Err(Ok(Error::InvalidConfig))
+    );
+    assert_eq!(
+        client.try_adjust_max_guesses(&1u32, &21u32),
+        Err(Ok(Error::InvalidConfig))
+    );
+
+    client.adjust_max_guesses(&1u32, &3u32);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    let expected_topics: Vec<Val> = (
+        symbol_short!("game"),
+        Symbol::new(&env, "max_guesses"),
+        1u32,
+    )
+        .into_val(&env);
+    assert_eq!(topics, expected_topics);
+    assert_eq!(u32::try_from_val(&env, &data).unwrap(), 3);
+
+    // Two more guesses now fit where the original cap allowed one
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &0u32, &proof_hash);
+    assert_eq!(client.get_game(&1u32).phase, GamePhase::WaitingForGuess);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &0u32, &proof_hash);
+    assert_eq!(client.get_finish_reason(&1u32), FinishReason::Exhausted);
+
+    assert_eq!(
+        client.try_adjust_max_guesses(&1u32, &4u32),
+        Err(Ok(Error::GameAlreadyEnded))
+    );
+}
+
+#[test]
+fn test_pending_payout_for_winner_loser_and_draw() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+    let treasury = Address::generate(&env);
+    client.set_treasury(&treasury);
+    client.set_resign_penalty(&1_000u32);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+
+    // Session 1 ends in a draw, session 2 in a resignation
+    let drawn = GameConfig {
+        max_guesses: 1,
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 400,
+        codebreaker_stake: 400,
+        draw_on_exhaustion: true,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &drawn);
+    client.accept_game(&1u32, &codebreaker);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &1u32, &proof_hash);
+
+    let resigned = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 300,
+        codebreaker_stake: 300,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &resigned);
+    client.accept_game(&2u32, &codebreaker);
+    client.commit_code(&2u32, &codemaker, &commitment);
+    assert_eq!(
+        client.try_pending_payout(&2u32, &codemaker),
+        Err(Ok(Error::InvalidPhase))
+    );
+    client.give_up(&2u32, &codebreaker);
+
+    assert_eq!(client.pending_payout(&1u32, &codemaker), 400);
+    assert_eq!(client.pending_payout(&1u32, &codebreaker), 400);
+    // 10% of the resigning codebreaker's stake goes to the treasury
+    assert_eq!(client.pending_payout(&2u32, &codemaker), 570);
+    assert_eq!(client.pending_payout(&2u32, &codebreaker), 0);
+
+    assert_eq!(client.withdraw_winnings(&2u32, &codemaker), 570);
+    assert_eq!(client.pending_payout(&2u32, &codemaker), 0);
+}
+
+#[test]
+fn test_stored_vk_is_passed_to_verifier() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let verifier = MockVerifierClient::new(&env, &env.register(MockVerifier, ()));
+    client.set_verifier(&verifier.address);
+    verifier.set_valid(&true);
+    assert_eq!(client.get_vk(), None);
+    assert_eq!(
+        client.try_set_vk(&Bytes::new(&env)),
+        Err(Ok(Error::InvalidConfig))
+    );
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof = Bytes::from_array(&env, &[0x42u8; 64]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+
+    // Without a stored key the verifier loads its own
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_proven_feedback(&1u32, &codemaker, &1u32, &0u32, &proof);
+    assert_eq!(verifier.last_vk(), None);
+
+    let vk = Bytes::from_array(&env, &[0x01u8; 96]);
+    client.set_vk(&vk);
+    assert_eq!(client.get_vk(), Some(vk.clone()));
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_proven_feedback(&1u32, &codemaker, &1u32, &0u32, &proof);
+    assert_eq!(verifier.last_vk(), Some(vk));
+
+    let rotated = Bytes::from_array(&env, &[0x02u8; 96]);
+    client.set_vk(&rotated);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_proven_feedback(&1u32, &codemaker, &1u32, &0u32, &proof);
+    assert_eq!(verifier.last_vk(), Some(rotated));
+    assert_eq!(client.get_game(&1u32).feedbacks.len(), 3);
+}
+
+#[test]
+fn test_phase_history_of_completed_game() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    client.new_game(&1u32, &codemaker, &codebreaker);
+    assert_eq!(
+        client.get_phase_history(&1u32),
+        Vec::from_array(&env, [GamePhase::WaitingForAccept])
+    );
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &0u32, &proof_hash);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    let proof_hash = BytesN::from_array(&env, &[0xCEu8; 32]);
+    client.submit_feedback(&1u32, &codemaker, &4u32, &0u32, &proof_hash);
+
+    let expected = Vec::from_array(
+        &env,
+        [
+            GamePhase::WaitingForAccept,
+            GamePhase::WaitingForCommitment,
+            GamePhase::WaitingForGuess,
+            GamePhase::WaitingForFeedback,
+            GamePhase::WaitingForGuess,
+            GamePhase::WaitingForFeedback,
+            GamePhase::Finished,
+        ],
+    );
+    assert_eq!(client.get_phase_history(&1u32), expected);
+}
+
+#[test]
+fn test_codemaker_ready_commits_before_accept() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+
+    let config = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 250,
+        codebreaker_stake: 250,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    assert_eq!(token.balance(&codemaker), 750);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    assert_eq!(
+        client.try_codemaker_ready(&1u32, &codebreaker, &commitment),
+        Err(Ok(Error::NotCodeMaker))
+    );
+    client.codemaker_ready(&1u32, &codemaker, &commitment);
+    assert_eq!(client.get_game(&1u32).phase, GamePhase::WaitingForAccept);
+    assert_eq!(
+        client.try_codemaker_ready(&1u32, &codemaker, &commitment),
+        Err(Ok(Error::AlreadyCommitted))
+    );
+
+    // Accepting skips the commitment phase entirely
+    client.accept_game(&1u32, &codebreaker);
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::WaitingForGuess);
+    assert_eq!(game.commitment, commitment);
+    assert_eq!(token.balance(&codebreaker), 750);
+    assert_eq!(token.balance(&client.address), 500);
+    assert_eq!(
+        client.try_codemaker_ready(&1u32, &codemaker, &commitment),
+        Err(Ok(Error::InvalidPhase))
+    );
+
+    // After acceptance it behaves like commit_code
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&2u32, &codebreaker);
+    assert_eq!(
+        client.get_game(&2u32).phase,
+        GamePhase::WaitingForCommitment
+    );
+    client.codemaker_ready(&2u32, &codemaker, &commitment);
+    assert_eq!(client.get_game(&2u32).phase, GamePhase::WaitingForGuess);
+}
+
+#[test]
+fn test_is_admin() {
+    let (env, client, codemaker, _codebreaker) = setup_test();
+
+    let admin = client.get_admin();
+    assert!(client.is_admin(&admin));
+    assert!(!client.is_admin(&codemaker));
+
+    let successor = Address::generate(&env);
+    client.set_admin(&successor);
+    assert!(client.is_admin(&successor));
+    assert!(!client.is_admin(&admin));
+}
+
+#[test]
+fn test_feedback_for_wrong_guess_index_is_rejected() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback_for_guess(&1u32, &codemaker, &0u32, &1u32, &0u32, &proof_hash);
+
+    // A proof for the first guess cannot answer the second
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    assert_eq!(
+        client.try_submit_feedback_for_guess(&1u32, &codemaker, &0u32, &1u32, &0u32, &proof_hash),
+        Err(Ok(Error::ProofIndexMismatch))
+    );
+    assert_eq!(
+        client.try_submit_feedback_for_guess(&1u32, &codemaker, &2u32, &1u32, &0u32, &proof_hash),
+        Err(Ok(Error::ProofIndexMismatch))
+    );
+    client.submit_feedback_for_guess(&1u32, &codemaker, &1u32, &1u32, &0u32, &proof_hash);
+    assert_eq!(client.get_game(&1u32).feedbacks.len(), 2);
+}
+
+#[test]
+fn test_compute_commitment_opens_with_reveal() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let salt = BytesN::from_array(&env, &[0x11u8; 32]);
+
+    let mut secret = Vec::new(&env);
+    secret.push_back(5u32);
+    secret.push_back(0u32);
+    secret.push_back(5u32);
+    secret.push_back(2u32);
+    let commitment = client.compute_commitment(&CommitScheme::Keccak256, &secret, &salt);
+    assert_ne!(
+        commitment,
+        client.compute_commitment(&CommitScheme::Sha256, &secret, &salt)
+    );
+
+    let config = GameConfig {
+        commit_scheme: CommitScheme::Keccak256,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    client.give_up(&1u32, &codebreaker);
+
+    client.reveal_code(&1u32, &codemaker, &secret, &salt);
+    assert_eq!(client.get_revealed_secret(&1u32), secret);
+}
+
+#[test]
+fn test_practice_game_played_solo_to_a_win() {
+    let (_env, client, _codemaker, player) = setup_test();
+
+    client.new_practice_game(&1u32, &player);
+    let game = client.get_game(&1u32);
+    assert_eq!(game.codemaker, client.address);
+    assert_eq!(game.phase, GamePhase::WaitingForGuess);
+    let secret = game.secret;
+    assert_eq!(secret.len(), 4);
+
+    // A guess one color off in the first peg is answered at once
+    let mut guess = secret.clone();
+    let first = secret.get_unchecked(0);
+    guess.set(0, (first + 1) % 6);
+    client.submit_guess(&1u32, &player, &guess);
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::WaitingForGuess);
+    let feedback = game.feedbacks.get_unchecked(0);
+    assert!(feedback.correct_position >= 3);
+    assert!(feedback.correct_position + feedback.correct_color <= 4);
+
+    client.submit_guess(&1u32, &player, &secret);
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::Finished);
+    assert_eq!(game.winner, Some(player));
+    assert_eq!(game.finish_reason, FinishReason::Solved);
+    assert_eq!(
+        client.try_report_result(&1u32),
+        Err(Ok(Error::PracticeGame))
+    );
+}
+
+#[test]
+fn test_report_result_maps_hub_failures() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let hub = FailingGameHubClient::new(&env, &env.register(FailingGameHub, ()));
+    client.set_hub(&hub.address);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    client.give_up(&1u32, &codebreaker);
+
+    assert_eq!(
+        client.try_report_result(&1u32),
+        Err(Ok(Error::HubCallFailed))
+    );
+    hub.set_rejecting();
+    assert_eq!(client.try_report_result(&1u32), Err(Ok(Error::HubRejected)));
+    assert!(!client.get_game(&1u32).reported);
+
+    env.as_contract(&client.address, || {
+        env.storage().instance().remove(&DataKey::GameHubAddress);
+    });
+    assert_eq!(client.try_report_result(&1u32), Err(Ok(Error::HubNotSet)));
+}
+
+#[test]
+fn test_phase_counts_follow_transitions() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    assert_eq!(client.get_phase_counts(), PhaseCounts::default());
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+
+    client.new_game(&1u32, &codemaker, &codebreaker);
+    client.new_game(&2u32, &codemaker, &codebreaker);
+    client.accept_game(&2u32, &codebreaker);
+    client.new_game_and_commit(&3u32, &codemaker, &codebreaker, &commitment);
+    client.new_game_and_commit(&4u32, &codemaker, &codebreaker, &commitment);
+    client.submit_guess(&4u32, &codebreaker, &guess);
+    client.new_game_and_commit(&5u32, &codemaker, &codebreaker, &commitment);
+    client.give_up(&5u32, &codebreaker);
+
+    assert_eq!(
+        client.get_phase_counts(),
+        PhaseCounts {
+            waiting_for_accept: 1,
+            waiting_for_commitment: 1,
+            waiting_for_guess: 1,
+            waiting_for_feedback: 1,
+            finished: 1,
+        }
+    );
+
+    // A game removed on timeout leaves the counts
+    env.ledger()
+        .set_sequence_number(env.ledger().sequence() + 17_281);
+    client.claim_accept_timeout(&1u32, &codemaker);
+    assert_eq!(client.get_phase_counts().waiting_for_accept, 0);
+}
+
+#[test]
+fn test_persisted_game_outlives_temporary_ttl() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    client.new_game_and_commit(&2u32, &codemaker, &codebreaker, &commitment);
+
+    let outsider = Address::generate(&env);
+    assert_eq!(
+        client.try_persist_game(&1u32, &outsider),
+        Err(Ok(Error::NotPlayer))
+    );
+    client.persist_game(&1u32, &codebreaker);
+    assert!(client.is_persistent(&1u32));
+    assert!(!client.is_persistent(&2u32));
+
+    // Play continues against the persistent copy
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    assert_eq!(client.get_game(&1u32).phase, GamePhase::WaitingForFeedback);
+
+    env.ledger()
+        .set_sequence_number(env.ledger().sequence() + 518_401);
+    assert_eq!(client.try_get_game(&2u32), Err(Ok(Error::GameNotFound)));
+    let game = client.get_game(&1u32);
+    assert_eq!(game.pending_guesses.len(), 1);
+}
+
+#[test]
+fn test_first_move_grace_applies_only_to_first_move() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+    client.set_timeout_config(&TimeoutConfig {
+        first_move_grace: 1_000,
+        ..client.get_timeout_config()
+    });
+    let move_timeout = client.get_timeout_config().move_timeout;
+    let feedback_timeout = client.get_timeout_config().feedback_timeout;
+
+    client.new_game(&1u32, &codemaker, &codebreaker);
+    client.accept_game(&1u32, &codebreaker);
+
+    // The commitment is the codemaker's first move and gets the grace
+    env.ledger().set_sequence_number(100 + move_timeout + 1);
+    assert_eq!(
+        client.try_claim_commit_timeout(&1u32, &codebreaker),
+        Err(Ok(Error::TimeoutNotReached))
+    );
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+
+    // Feedback is not a first move: the usual timeout applies
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    let guessed_at = env.ledger().sequence();
+    env.ledger()
+        .set_sequence_number(guessed_at + feedback_timeout + 1);
+    client.claim_feedback_timeout(&1u32, &codebreaker);
+    assert_eq!(client.get_game(&1u32).winner, Some(codebreaker.clone()));
+
+    // The grace only pushes the deadline back
+    client.new_game(&2u32, &codemaker, &codebreaker);
+    client.accept_game(&2u32, &codebreaker);
+    let accepted_at = env.ledger().sequence();
+    env.ledger()
+        .set_sequence_number(accepted_at + move_timeout + 1_001);
+    client.claim_commit_timeout(&2u32, &codebreaker);
+    assert_eq!(client.try_get_game(&2u32), Err(Ok(Error::GameNotFound)));
+}
+
+#[test]
+fn test_rules_digest_tracks_every_rule() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &test_config(&env));
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &test_config(&env));
+    let digest = client.rules_digest(&1u32);
+    assert_eq!(client.rules_digest(&1u32), digest);
+    assert_eq!(client.rules_digest(&2u32), digest);
+
+    let variants = [
+        GameConfig {
+            code_length: 5,
+            ..test_config(&env)
+        },
+        GameConfig {
+            num_colors: 7,
+            ..test_config(&env)
+        },
+        GameConfig {
+            max_guesses: 10,
+            ..test_config(&env)
+        },
+        GameConfig {
+            feedback_mode: FEEDBACK_COMBINED,
+            ..test_config(&env)
+        },
+        GameConfig {
+            hints_allowed: true,
+            ..test_config(&env)
+        },
+    ];
+    for (session_id, config) in (3u32..).zip(variants) {
+        client.new_game_with_config(&session_id, &codemaker, &codebreaker, &config);
+        assert_ne!(client.rules_digest(&session_id), digest);
+    }
+
+    // Raising the guess limit mid-game is a rule change too
+    client.adjust_max_guesses(&2u32, &13u32);
+    assert_ne!(client.rules_digest(&2u32), digest);
+}
+
+#[test]
+fn test_closest_guess_after_exhaustion() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let config = GameConfig {
+        max_guesses: 4,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+
+    // (red, white) per guess; the third is closest, the fourth ties on red
+    let answers = [(1u32, 1u32), (2, 0), (3, 0), (3, 0)];
+    for (i, (red, white)) in answers.iter().enumerate() {
+        let mut guess = Vec::new(&env);
+        for peg in 0..4u32 {
+            guess.push_back((peg + i as u32) % 6);
+        }
+        client.submit_guess(&1u32, &codebreaker, &guess);
+        assert_eq!(
+            client.try_closest_guess(&1u32),
+            Err(Ok(Error::InvalidPhase))
+        );
+        let proof_hash = BytesN::from_array(&env, &[i as u8 + 1; 32]);
+        client.submit_feedback(&1u32, &codemaker, red, white, &proof_hash);
+    }
+    assert_eq!(client.get_finish_reason(&1u32), FinishReason::Exhausted);
+
+    let mut expected = Vec::new(&env);
+    for color in [2u32, 3, 4, 5] {
+        expected.push_back(color);
+    }
+    assert_eq!(client.closest_guess(&1u32), (expected, 3u32, 0u32));
+}
+
+#[test]
+fn test_dual_game_fewer_guesses_wins() {
+    let (env, client, player_a, player_b) = setup_test();
+
+    let dual_id = client.new_dual_game(&player_a, &player_b);
+    let dual = client.get_dual_game(&dual_id);
+    assert_eq!(
+        dual,
+        DualGame {
+            player_a: player_a.clone(),
+            player_b: player_b.clone(),
+            game_a: dual.game_a,
+            game_b: dual.game_b,
+            decided: false,
+            winner: None,
+        }
+    );
+    let game_a = client.get_game(&dual.game_a);
+    assert_eq!(game_a.codemaker, player_a);
+    assert_eq!(game_a.phase, GamePhase::WaitingForCommitment);
+    assert_eq!(client.get_game(&dual.game_b).codemaker, player_b);
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&dual.game_a, &player_a, &commitment);
+    client.commit_code(&dual.game_b, &player_b, &commitment);
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let mut proof = 0u8;
+    let mut answer = |session_id: u32, codemaker: &Address, codebreaker: &Address, red: u32| {
+        proof += 1;
+        let proof_hash = BytesN::from_array(&env, &[proof; 32]);
+        client.submit_guess(&session_id, codebreaker, &guess);
+        client.submit_feedback(&session_id, codemaker, &red, &0u32, &proof_hash);
+    };
+
+    // A cracks B's code on the second guess
+    answer(dual.game_b, &player_b, &player_a, 1);
+    answer(dual.game_b, &player_b, &player_a, 4);
+    answer(dual.game_a, &player_a, &player_b, 2);
+    assert_eq!(
+        client.try_resolve_dual_game(&dual_id),
+        Err(Ok(Error::InvalidPhase))
+    );
+
+    // B misses with their second guess too, so B can no longer match A
+    answer(dual.game_a, &player_a, &player_b, 3);
+    client.resolve_dual_game(&dual_id);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    let expected_topics: Vec<Val> =
+        (symbol_short!("dual"), symbol_short!("decided"), dual_id).into_val(&env);
+    assert_eq!(topics, expected_topics);
+    assert_eq!(
+        Option::<Address>::try_from_val(&env, &data).unwrap(),
+        Some(player_a.clone())
+    );
+
+    let dual = client.get_dual_game(&dual_id);
+    assert!(dual.decided);
+    assert_eq!(dual.winner, Some(player_a));
+    assert_eq!(
+        client.try_resolve_dual_game(&dual_id),
+        Err(Ok(Error::GameAlreadyEnded))
+    );
+}
+
+#[test]
+fn test_ledgers_until_timeout_counts_down() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+    let feedback_timeout = client.get_timeout_config().feedback_timeout;
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    assert_eq!(client.ledgers_until_timeout(&1u32), feedback_timeout + 1);
+
+    env.ledger().set_sequence_number(150);
+    assert_eq!(client.ledgers_until_timeout(&1u32), feedback_timeout - 49);
+
+    env.ledger().set_sequence_number(100 + feedback_timeout);
+    assert_eq!(client.ledgers_until_timeout(&1u32), 1);
+    assert_eq!(
+        client.try_claim_feedback_timeout(&1u32, &codebreaker),
+        Err(Ok(Error::TimeoutNotReached))
+    );
+
+    env.ledger().set_sequence_number(101 + feedback_timeout);
+    assert_eq!(client.ledgers_until_timeout(&1u32), 0);
+    client.claim_feedback_timeout(&1u32, &codebreaker);
+    assert_eq!(
+        client.try_ledgers_until_timeout(&1u32),
+        Err(Ok(Error::InvalidPhase))
+    );
+}
+
+#[test]
+fn test_color_labels_validated_and_looked_up() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let mut labels = Vec::new(&env);
+    for name in ["red", "blue", "green", "yellow", "orange", "purple"] {
+        labels.push_back(String::from_str(&env, name));
+    }
+    let config = GameConfig {
+        color_labels: labels.clone(),
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    assert_eq!(
+        client.get_color_label(&1u32, &2u32),
+        String::from_str(&env, "green")
+    );
+    assert_eq!(
+        client.try_get_color_label(&1u32, &6u32),
+        Err(Ok(Error::InvalidGuessValue))
+    );
+
+    let mut duplicated = labels.clone();
+    duplicated.set(5, String::from_str(&env, "red"));
+    let config = GameConfig {
+        color_labels: duplicated,
+        ..test_config(&env)
+    };
+    assert_eq!(
+        client.try_new_game_with_config(&2u32, &codemaker, &codebreaker, &config),
+        Err(Ok(Error::InvalidLabels))
+    );
+
+    let mut blank = labels;
+    blank.set(3, String::from_str(&env, ""));
+    let config = GameConfig {
+        color_labels: blank,
+        ..test_config(&env)
+    };
+    assert_eq!(
+        client.try_new_game_with_config(&3u32, &codemaker, &codebreaker, &config),
+        Err(Ok(Error::InvalidLabels))
+    );
+}
+
+#[test]
+fn test_stake_token_allowlist() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let listed = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+    let unlisted = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+    let staked_in = |token: &TokenClient| GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 100,
+        codebreaker_stake: 100,
+        ..test_config(&env)
+    };
+
+    // An empty allowlist accepts any token
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &staked_in(&unlisted));
+
+    client.allow_token(&listed.address);
+    assert_eq!(
+        client.try_new_game_with_config(&2u32, &codemaker, &codebreaker, &staked_in(&unlisted)),
+        Err(Ok(Error::TokenNotAllowed))
+    );
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &staked_in(&listed));
+    // Unstaked games do not need a listed token
+    client.new_game_with_config(&3u32, &codemaker, &codebreaker, &test_config(&env));
+
+    client.disallow_token(&listed.address);
+    assert_eq!(client.get_allowed_tokens().len(), 0);
+    client.new_game_with_config(&4u32, &codemaker, &codebreaker, &staked_in(&unlisted));
+}
+
+#[test]
+fn test_legal_colors_match_config() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let config = GameConfig {
+        num_colors: 3,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+
+    let mut expected = Vec::new(&env);
+    expected.push_back(0u32);
+    expected.push_back(1u32);
+    expected.push_back(2u32);
+    assert_eq!(client.legal_colors(&1u32), expected);
+    assert_eq!(client.try_legal_colors(&2u32), Err(Ok(Error::GameNotFound)));
+}
+
+#[test]
+fn test_poke_cancels_after_commit_deadline() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+
+    let config = GameConfig {
+        commit_deadline_ledgers: Some(10),
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+
+    env.ledger().set_sequence_number(110);
+    assert!(!client.poke(&1u32));
+    assert_eq!(
+        client.get_game(&1u32).phase,
+        GamePhase::WaitingForCommitment
+    );
+
+    env.ledger().set_sequence_number(111);
+    assert!(client.poke(&1u32));
+    assert_eq!(client.try_get_game(&1u32), Err(Ok(Error::GameNotFound)));
+    assert_eq!(client.try_poke(&1u32), Err(Ok(Error::GameNotFound)));
+}
+
+#[test]
+fn test_poke_awards_feedback_timeout() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+    let feedback_timeout = client.get_timeout_config().feedback_timeout;
+
+    let config = GameConfig {
+        commit_deadline_ledgers: Some(10),
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+
+    // The commit deadline no longer applies once the secret is committed
+    env.ledger().set_sequence_number(100 + feedback_timeout);
+    assert!(!client.poke(&1u32));
+
+    env.ledger().set_sequence_number(101 + feedback_timeout);
+    assert!(client.poke(&1u32));
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::Finished);
+    assert_eq!(game.winner, Some(codebreaker));
+    assert!(!client.poke(&1u32));
+}
+
+#[test]
+fn test_caught_lie_lowers_honesty() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let salt = BytesN::from_array(&env, &[0x11u8; 32]);
+
+    let mut secret = Vec::new(&env);
+    secret.push_back(0u32);
+    secret.push_back(1u32);
+    secret.push_back(2u32);
+    secret.push_back(3u32);
+    let commitment = sha256_commitment(&env, &secret, &salt);
+    let strict = GameConfig {
+        strict_proofs: true,
+        ..test_config(&env)
+    };
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(3u32);
+    guess.push_back(1u32);
+    guess.push_back(4u32);
+    guess.push_back(4u32);
+    // The true answer is 1 red, 1 white; the last game lies about it
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    for (session_id, red, white, honesty) in [(1u32, 1u32, 1u32, 1i32), (2, 1, 1, 2), (3, 0, 1, 1)]
+    {
+        client.new_game_with_config(&session_id, &codemaker, &codebreaker, &strict);
+        client.accept_game(&session_id, &codebreaker);
+        client.commit_code(&session_id, &codemaker, &commitment);
+        client.submit_guess(&session_id, &codebreaker, &guess);
+        client.submit_feedback(&session_id, &codemaker, &red, &white, &proof_hash);
+        client.give_up(&session_id, &codebreaker);
+        client.reveal_code(&session_id, &codemaker, &secret, &salt);
+        assert_eq!(client.get_stats(&codemaker).honesty, honesty);
+    }
+
+    let stats = client.get_stats(&codemaker);
+    assert_eq!((stats.clean_reveals, stats.caught_lies), (2, 1));
+    assert_eq!(client.get_stats(&codebreaker), PlayerStats::default());
+}
+
+#[test]
+fn test_new_games_seeds_a_batch() {
+    let (env, client, _codemaker, _codebreaker) = setup_test();
+
+    let players: [Address; 8] = core::array::from_fn(|_| Address::generate(&env));
+    let mut specs = Vec::new(&env);
+    for pair in players.chunks(2) {
+        specs.push_back(GameSpec {
+            codemaker: pair[0].clone(),
+            codebreaker: pair[1].clone(),
+            config: GameConfig {
+                max_guesses: 8,
+                ..test_config(&env)
+            },
+        });
+    }
+    let sessions = client.new_games(&specs);
+    assert_eq!(sessions.len(), 4);
+
+    for (session_id, spec) in sessions.iter().zip(specs.iter()) {
+        let game = client.get_game(&session_id);
+        assert_eq!(game.codemaker, spec.codemaker);
+        assert_eq!(game.codebreaker, spec.codebreaker);
+        assert_eq!(game.phase, GamePhase::WaitingForAccept);
+        assert_eq!(game.max_guesses, 8);
+    }
+    // Ids are assigned fresh, so a second batch never collides with the first
+    let again = client.new_games(&specs);
+    for session_id in again.iter() {
+        assert!(!sessions.contains(session_id));
+    }
+
+    let mut oversized = Vec::new(&env);
+    for _ in 0..17 {
+        oversized.push_back(specs.get_unchecked(0));
+    }
+    assert_eq!(
+        client.try_new_games(&oversized),
+        Err(Ok(Error::BatchTooLarge))
+    );
+}
+
+#[test]
+fn test_check_result_against_claimed_hash() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    let claimed = BytesN::from_array(&env, &[0x42u8; 32]);
+    assert_eq!(
+        client.try_check_result(&1u32, &claimed),
+        Err(Ok(Error::InvalidPhase))
+    );
+
+    client.give_up(&1u32, &codebreaker);
+    let actual = client.result_hash(&1u32);
+    assert!(client.check_result(&1u32, &actual));
+    assert!(!client.check_result(&1u32, &claimed));
+}
+
+#[test]
+fn test_existing_session_cannot_be_overwritten() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+    let config = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 100,
+        codebreaker_stake: 100,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+
+    let attacker = Address::generate(&env);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    assert_eq!(
+        client.try_new_game(&1u32, &attacker, &codebreaker),
+        Err(Ok(Error::GameAlreadyExists))
+    );
+    assert_eq!(
+        client.try_new_game_and_commit(&1u32, &attacker, &codebreaker, &commitment),
+        Err(Ok(Error::GameAlreadyExists))
+    );
+    assert_eq!(
+        client.try_new_practice_game(&1u32, &attacker),
+        Err(Ok(Error::GameAlreadyExists))
+    );
+
+    let game = client.get_game(&1u32);
+    assert_eq!(game.codemaker, codemaker);
+    assert_eq!(game.phase, GamePhase::WaitingForCommitment);
+    assert_eq!(client.total_escrow(&token.address), 200);
+}
+
+#[test]
+fn test_combined_score_permutation_is_not_a_win() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let salt = BytesN::from_array(&env, &[0x11u8; 32]);
+
+    let mut secret = Vec::new(&env);
+    secret.push_back(0u32);
+    secret.push_back(1u32);
+    secret.push_back(2u32);
+    secret.push_back(3u32);
+    let commitment = sha256_commitment(&env, &secret, &salt);
+    let combined = GameConfig {
+        feedback_mode: FEEDBACK_COMBINED,
+        strict_proofs: true,
+        ..test_config(&env)
+    };
+    let mut permutation = Vec::new(&env);
+    permutation.push_back(3u32);
+    permutation.push_back(2u32);
+    permutation.push_back(1u32);
+    permutation.push_back(0u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &combined);
+    client.accept_game(&1u32, &codebreaker);
+    client.commit_code(&1u32, &codemaker, &commitment);
+
+    // A full score on a permutation of the secret plays on
+    client.submit_guess(&1u32, &codebreaker, &permutation);
+    assert_eq!(
+        client.try_submit_score(&1u32, &codemaker, &3u32, &true, &proof_hash),
+        Err(Ok(Error::InvalidFeedback))
+    );
+    client.submit_score(&1u32, &codemaker, &4u32, &false, &proof_hash);
+    assert_eq!(client.get_game(&1u32).phase, GamePhase::WaitingForGuess);
+
+    client.submit_guess(&1u32, &codebreaker, &secret);
+    let proof_hash = BytesN::from_array(&env, &[0xCEu8; 32]);
+    client.submit_score(&1u32, &codemaker, &4u32, &true, &proof_hash);
+    let game = client.get_game(&1u32);
+    assert_eq!(game.phase, GamePhase::Finished);
+    assert_eq!(game.finish_reason, FinishReason::Solved);
+    assert_eq!(game.winner, Some(codebreaker.clone()));
+    client.reveal_code(&1u32, &codemaker, &secret, &salt);
+    assert_eq!(client.get_game(&1u32).finish_reason, FinishReason::Solved);
+
+    // Denying the win on the real secret is caught at reveal
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &combined);
+    client.accept_game(&2u32, &codebreaker);
+    client.commit_code(&2u32, &codemaker, &commitment);
+    client.submit_guess(&2u32, &codebreaker, &secret);
+    client.submit_score(&2u32, &codemaker, &4u32, &false, &proof_hash);
+    client.give_up(&2u32, &codebreaker);
+    client.reveal_code(&2u32, &codemaker, &secret, &salt);
+    let game = client.get_game(&2u32);
+    assert_eq!(game.finish_reason, FinishReason::FalseFeedback);
+    assert_eq!(game.winner, Some(codebreaker));
+}
+
+#[test]
+fn test_dispute_window_holds_payouts() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+    client.set_timeout_config(&TimeoutConfig {
+        dispute_window: 50,
+        ..client.get_timeout_config()
+    });
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+    let config = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 100,
+        codebreaker_stake: 100,
+        ..test_config(&env)
+    };
+    let secret = Vec::from_array(&env, [0u32, 1, 2, 3]);
+    let salt = BytesN::from_array(&env, &[0x11u8; 32]);
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = sha256_commitment(&env, &secret, &salt);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    client.give_up(&1u32, &codebreaker);
+
+    assert_eq!(
+        client.try_withdraw_winnings(&1u32, &codemaker),
+        Err(Ok(Error::DisputeWindowOpen))
+    );
+    assert_eq!(
+        client.try_pending_payout(&1u32, &codemaker),
+        Err(Ok(Error::DisputeWindowOpen))
+    );
+    assert_eq!(client.settle_many(&Vec::from_array(&env, [1u32])), 0);
+    assert_eq!(token.balance(&client.address), 200);
+
+    client.reveal_code(&1u32, &codemaker, &secret, &salt);
+    env.ledger().set_sequence_number(150);
+    assert_eq!(client.pending_payout(&1u32, &codemaker), 200);
+    assert_eq!(client.withdraw_winnings(&1u32, &codemaker), 200);
+    assert_eq!(token.balance(&codemaker), 1_100);
+}
+
+#[test]
+fn test_verify_transcript_rejects_out_of_range_counts() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_feedback(&1u32, &codemaker, &1u32, &1u32, &proof_hash);
+    let transcript = client.export_transcript(&1u32);
+
+    for (red, white) in [(u32::MAX, 1u32), (1, u32::MAX), (3, 2)] {
+        let mut tampered = transcript.clone();
+        tampered.feedbacks.set(
+            0,
+            Feedback {
+                correct_position: red,
+                correct_color: white,
+                proof_hash: proof_hash.clone(),
+            },
+        );
+        assert_eq!(client.try_verify_transcript(&tampered), Ok(Ok(false)));
+    }
+}
+
+#[test]
+fn test_poke_applies_phase_timeouts() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+    let timeouts = client.get_timeout_config();
+
+    // An unanswered invitation lapses after the accept window
+    client.new_game(&1u32, &codemaker, &codebreaker);
+    env.ledger()
+        .set_sequence_number(100 + timeouts.accept_window);
+    assert!(!client.poke(&1u32));
+    env.ledger()
+        .set_sequence_number(101 + timeouts.accept_window);
+    assert!(client.poke(&1u32));
+    assert_eq!(client.try_get_game(&1u32), Err(Ok(Error::GameNotFound)));
+
+    // A far-off commit deadline neither overflows nor outlasts the usual
+    // commit timeout
+    env.ledger().set_sequence_number(100);
+    let config = GameConfig {
+        commit_deadline_ledgers: Some(u32::MAX),
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&2u32, &codebreaker);
+    let commit_timeout = timeouts.move_timeout + timeouts.first_move_grace;
+    env.ledger().set_sequence_number(100 + commit_timeout);
+    assert!(!client.poke(&2u32));
+    env.ledger().set_sequence_number(101 + commit_timeout);
+    assert!(client.poke(&2u32));
+    assert_eq!(client.try_get_game(&2u32), Err(Ok(Error::GameNotFound)));
+}
+
+#[test]
+fn test_observer_sees_reveals_and_cancellations() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+    let observer = MockObserverClient::new(&env, &env.register(MockObserver, ()));
+    client.set_observer(&Some(observer.address.clone()));
+    let salt = BytesN::from_array(&env, &[0x11u8; 32]);
+
+    let mut secret = Vec::new(&env);
+    secret.push_back(0u32);
+    secret.push_back(1u32);
+    secret.push_back(2u32);
+    secret.push_back(3u32);
+    let commitment = sha256_commitment(&env, &secret, &salt);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    client.give_up(&1u32, &codebreaker);
+    let seen = observer.moves().len();
+    client.reveal_code(&1u32, &codemaker, &secret, &salt);
+    assert_eq!(observer.moves().len(), seen + 1);
+
+    // A cancelled game is reported in the phase it was cancelled from
+    client.new_game(&2u32, &codemaker, &codebreaker);
+    let accept_window = client.get_timeout_config().accept_window;
+    env.ledger().set_sequence_number(101 + accept_window);
+    client.claim_accept_timeout(&2u32, &codemaker);
+    assert_eq!(
+        observer.moves().last(),
+        Some((2u32, GamePhase::WaitingForAccept, 0u32))
+    );
+}
+
+#[test]
+fn test_walkover_unblocks_tournament() {
+    let (env, client, _codemaker, _codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+
+    let mut players = Vec::new(&env);
+    for _ in 0..2 {
+        players.push_back(Address::generate(&env));
+    }
+    let tournament_id = client.new_tournament(&players);
+    let final_id = client
+        .get_tournament(&tournament_id)
+        .matches
+        .get_unchecked(0);
+    let codemaker = players.get_unchecked(0);
+
+    // A live match cannot be awarded
+    assert_eq!(
+        client.try_award_walkover(&tournament_id, &final_id, &codemaker),
+        Err(Ok(Error::InvalidPhase))
+    );
+
+    // The codebreaker never accepts, so the match is cancelled
+    let accept_window = client.get_timeout_config().accept_window;
+    env.ledger().set_sequence_number(101 + accept_window);
+    client.claim_accept_timeout(&final_id, &codemaker);
+    assert_eq!(
+        client.try_advance_tournament(&tournament_id),
+        Err(Ok(Error::GameNotFound))
+    );
+
+    let outsider = Address::generate(&env);
+    assert_eq!(
+        client.try_award_walkover(&tournament_id, &final_id, &outsider),
+        Err(Ok(Error::NotPlayer))
+    );
+    client.award_walkover(&tournament_id, &final_id, &codemaker);
+    client.advance_tournament(&tournament_id);
+    assert_eq!(
+        client.get_tournament(&tournament_id).champion,
+        Some(codemaker)
+    );
+}
+
+#[test]
+fn test_new_games_respects_game_cap() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    client.set_max_games_per_player(&1u32);
+
+    let other = Address::generate(&env);
+    let spec = GameSpec {
+        codemaker: codemaker.clone(),
+        codebreaker: other.clone(),
+        config: test_config(&env),
+    };
+    let second = GameSpec {
+        codebreaker,
+        ..spec.clone()
+    };
+    assert_eq!(
+        client.try_new_games(&Vec::from_array(&env, [spec.clone(), second])),
+        Err(Ok(Error::TooManyActiveGames))
+    );
+    assert_eq!(client.active_game_between(&codemaker, &other), None);
+
+    let sessions = client.new_games(&Vec::from_array(&env, [spec]));
+    assert_eq!(
+        client.active_game_between(&codemaker, &other),
+        sessions.first()
+    );
+}
+
+#[test]
+fn test_dual_game_stalled_or_cancelled_sessions() {
+    let (env, client, player_a, player_b) = setup_test();
+    env.ledger().set_sequence_number(100);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+
+    // A stalls the feedback on their own code, then solves B's
+    let stalled = client.new_dual_game(&player_a, &player_b);
+    let dual = client.get_dual_game(&stalled);
+    client.commit_code(&dual.game_a, &player_a, &commitment);
+    client.commit_code(&dual.game_b, &player_b, &commitment);
+    client.submit_guess(&dual.game_a, &player_b, &guess);
+    let feedback_timeout = client.get_timeout_config().feedback_timeout;
+    env.ledger().set_sequence_number(101 + feedback_timeout);
+    assert!(client.poke(&dual.game_a));
+    client.submit_guess(&dual.game_b, &player_a, &guess);
+    client.submit_feedback(&dual.game_b, &player_b, &4u32, &0u32, &proof_hash);
+    client.resolve_dual_game(&stalled);
+    assert_eq!(
+        client.get_dual_game(&stalled).winner,
+        Some(player_b.clone())
+    );
+
+    // B never commits, so that session is cancelled and the dual is void
+    let void = client.new_dual_game(&player_a, &player_b);
+    let dual = client.get_dual_game(&void);
+    client.commit_code(&dual.game_a, &player_a, &commitment);
+    let timeouts = client.get_timeout_config();
+    env.ledger().set_sequence_number(
+        102 + feedback_timeout + timeouts.move_timeout + timeouts.first_move_grace,
+    );
+    assert!(client.poke(&dual.game_b));
+    client.resolve_dual_game(&void);
+    let dual = client.get_dual_game(&void);
+    assert!(dual.decided);
+    assert_eq!(dual.winner, None);
+}
+
+#[test]
+fn test_dual_game_counts_both_sessions_against_cap() {
+    let (env, client, player_a, player_b) = setup_test();
+    client.set_max_games_per_player(&2u32);
+
+    client.new_game(&1u32, &player_a, &Address::generate(&env));
+    assert_eq!(
+        client.try_new_dual_game(&player_a, &player_b),
+        Err(Ok(Error::TooManyActiveGames))
+    );
+
+    client.set_max_games_per_player(&3u32);
+    client.new_dual_game(&player_a, &player_b);
+}
+
+#[test]
+fn test_verify_transcript_combined_mode() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    let combined = GameConfig {
+        feedback_mode: FEEDBACK_COMBINED,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &combined);
+    client.accept_game(&1u32, &codebreaker);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    let mut permutation = Vec::new(&env);
+    permutation.push_back(3u32);
+    permutation.push_back(2u32);
+    permutation.push_back(1u32);
+    permutation.push_back(0u32);
+    let proof_hash = BytesN::from_array(&env, &[0xCDu8; 32]);
+
+    // A full score on a permutation is not a solve
+    client.submit_guess(&1u32, &codebreaker, &permutation);
+    client.submit_score(&1u32, &codemaker, &4u32, &false, &proof_hash);
+    let transcript = client.export_transcript(&1u32);
+    assert_eq!(transcript.feedback_mode, FEEDBACK_COMBINED);
+    assert_eq!(transcript.solved_at, None);
+    assert!(client.verify_transcript(&transcript));
+
+    let mut tampered = transcript.clone();
+    tampered.finish_reason = FinishReason::Solved;
+    tampered.winner = Some(codebreaker.clone());
+    assert!(!client.verify_transcript(&tampered));
+
+    let mut guess = Vec::new(&env);
+    guess.push_back(0u32);
+    guess.push_back(1u32);
+    guess.push_back(2u32);
+    guess.push_back(3u32);
+    client.submit_guess(&1u32, &codebreaker, &guess);
+    client.submit_score(&1u32, &codemaker, &4u32, &true, &proof_hash);
+    let transcript = client.export_transcript(&1u32);
+    assert_eq!(transcript.solved_at, Some(1));
+    assert!(client.verify_transcript(&transcript));
+
+    // The same transcript read as split feedback would have ended at the
+    // first full score
+    let mut tampered = transcript;
+    tampered.feedback_mode = FEEDBACK_SPLIT;
+    assert!(!client.verify_transcript(&tampered));
+}
+
+#[test]
+fn test_huge_timeout_windows_never_expire() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+    client.set_timeout_config(&TimeoutConfig {
+        move_timeout: u32::MAX,
+        feedback_timeout: u32::MAX,
+        abandonment_window: u32::MAX,
+        accept_window: u32::MAX,
+        dispute_window: 0,
+        first_move_grace: u32::MAX,
+    });
+
+    client.new_game(&1u32, &codemaker, &codebreaker);
+    client.accept_game(&1u32, &codebreaker);
+    env.ledger().set_sequence_number(1_000);
+    assert!(!client.poke(&1u32));
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    assert!(!client.poke(&1u32));
+    assert_eq!(client.get_game(&1u32).phase, GamePhase::WaitingForGuess);
+    assert_eq!(client.ledgers_until_timeout(&1u32), u32::MAX - 1_000);
+}
+
+#[test]
+fn test_unrevealed_win_goes_to_codebreaker() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+    client.set_timeout_config(&TimeoutConfig {
+        dispute_window: 50,
+        ..client.get_timeout_config()
+    });
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+    let config = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 100,
+        codebreaker_stake: 100,
+        ..test_config(&env)
+    };
+    let secret = Vec::from_array(&env, [0u32, 1, 2, 3]);
+    let salt = BytesN::from_array(&env, &[0x11u8; 32]);
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &config);
+    client.accept_game(&1u32, &codebreaker);
+    client.commit_code(&1u32, &codemaker, &sha256_commitment(&env, &secret, &salt));
+    client.give_up(&1u32, &codebreaker);
+    assert_eq!(
+        client.try_claim_reveal_timeout(&1u32, &codebreaker),
+        Err(Ok(Error::TimeoutNotReached))
+    );
+
+    // With no reveal, the codemaker's win is never paid or reported
+    env.ledger().set_sequence_number(150);
+    assert_eq!(
+        client.try_withdraw_winnings(&1u32, &codemaker),
+        Err(Ok(Error::RevealPending))
+    );
+    assert_eq!(
+        client.try_report_result(&1u32),
+        Err(Ok(Error::RevealPending))
+    );
+    assert_eq!(client.settle_many(&Vec::from_array(&env, [1u32])), 0);
+    assert_eq!(
+        client.try_reveal_code(&1u32, &codemaker, &secret, &salt),
+        Err(Ok(Error::InvalidPhase))
+    );
+
+    client.claim_reveal_timeout(&1u32, &codebreaker);
+    let game = client.get_game(&1u32);
+    assert_eq!(game.winner, Some(codebreaker.clone()));
+    assert_eq!(game.finish_reason, FinishReason::Timeout);
+    assert_eq!(client.withdraw_winnings(&1u32, &codebreaker), 200);
+    assert_eq!(token.balance(&codebreaker), 1_100);
+}
+
+#[test]
+fn test_history_records_overturned_result() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    env.ledger().set_sequence_number(100);
+    client.set_timeout_config(&TimeoutConfig {
+        dispute_window: 50,
+        ..client.get_timeout_config()
+    });
+    let pair = |left: &BytesN<32>, right: &BytesN<32>| -> BytesN<32> {
+        let mut preimage = Bytes::from_array(&env, &left.to_array());
+        preimage.extend_from_array(&right.to_array());
+        env.crypto().sha256(&preimage).into()
+    };
+
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    client.give_up(&1u32, &codebreaker);
+    let first = client.result_hash(&1u32);
+    assert_eq!(client.get_history_root(), first);
+
+    // The codemaker never reveals, so the win moves to the codebreaker
+    env.ledger().set_sequence_number(150);
+    client.claim_reveal_timeout(&1u32, &codebreaker);
+    let corrected = client.result_hash(&1u32);
+    assert_ne!(corrected, first);
+    assert_eq!(client.get_history_root(), pair(&first, &corrected));
+}
+
+#[test]
+fn test_cancel_stale_skips_unanswered_guess() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+    let staked = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 300,
+        codebreaker_stake: 200,
+        ..test_config(&env)
+    };
+    client.new_game_with_config(&1u32, &codemaker, &codebreaker, &staked);
+    client.accept_game(&1u32, &codebreaker);
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.commit_code(&1u32, &codemaker, &commitment);
+    client.submit_guess(&1u32, &codebreaker, &Vec::from_array(&env, [0u32, 1, 2, 3]));
+
+    // The codemaker cannot dodge the first feedback by cancelling
+    let window = client.get_timeout_config().abandonment_window;
+    env.ledger().set_sequence_number(window + 1);
+    assert_eq!(client.cancel_stale(&codemaker), 0);
+    assert_eq!(client.get_game(&1u32).phase, GamePhase::WaitingForFeedback);
+
+    client.claim_feedback_timeout(&1u32, &codebreaker);
+    assert_eq!(client.get_game(&1u32).winner, Some(codebreaker));
+}
+
+#[test]
+fn test_tournament_rounds_respect_game_cap() {
+    let (env, client, _codemaker, _codebreaker) = setup_test();
+    client.set_max_games_per_player(&1u32);
+
+    let mut players = Vec::new(&env);
+    for _ in 0..2 {
+        players.push_back(Address::generate(&env));
+    }
+    client.new_game(&1u32, &players.get_unchecked(0), &Address::generate(&env));
+    assert_eq!(
+        client.try_new_tournament(&players),
+        Err(Ok(Error::TooManyActiveGames))
+    );
+
+    client.set_max_games_per_player(&2u32);
+    let tournament_id = client.new_tournament(&players);
+    assert_eq!(client.get_tournament(&tournament_id).matches.len(), 1);
+}
+
+#[test]
+fn test_settle_many_skips_failed_hub_reports() {
+    let (env, client, codemaker, codebreaker) = setup_test();
+    let token = setup_stake_token(&env, &codemaker, &codebreaker, 1_000);
+    let staked = GameConfig {
+        stake_token: Some(token.address.clone()),
+        codemaker_stake: 100,
+        codebreaker_stake: 100,
+        ..test_config(&env)
+    };
+    let commitment = BytesN::from_array(&env, &[0xABu8; 32]);
+    client.new_game_and_commit(&1u32, &codemaker, &codebreaker, &commitment);
+    client.give_up(&1u32, &codebreaker);
+    client.new_game_with_config(&2u32, &codemaker, &codebreaker, &staked);
+    client.accept_game(&2u32, &codebreaker);
+    client.commit_code(&2u32, &codemaker, &commitment);
+    client.give_up(&2u32, &codebreaker);
+    client.report_result(&2u32);
+
+    let hub = FailingGameHubClient::new(&env, &env.register(FailingGameHub, ()));
+    client.set_hub(&hub.address);
+
+    // Game 1 cannot be reported, but game 2 still gets paid
+    assert_eq!(client.settle_many(&Vec::from_array(&env, [1u32, 2])), 1);
+    assert!(!client.get_game(&1u32).reported);
+    assert!(client.get_game(&2u32).settled);
+    assert_eq!(token.balance(&codemaker), 1_100);
+}